@@ -1,9 +1,27 @@
+use egui::collapsing_header::CollapsingState;
 use egui_notify::Toasts;
 use std::collections::HashMap;
-use std::env;
+use std::path::PathBuf;
+
+use crate::{
+    database::{merge_gmst_dbs, GmstDb, GmstDisagreement},
+    grouping::{group_gmsts, EGmstGrouping, GmstGroup},
+    history::{EHistoryCommand, GmstChange, History},
+    metadata::GmstMetadata,
+    profile::{get_unique_profile_name, is_profile_name_taken, GmstProfile, DEFAULT_PROFILE_NAME},
+    EGmstValue, EScale, ESectionFilter, ETheme, Gmst, GmstConflict, GmstName, GmstViewModel,
+    ModViewModel,
+};
 
-use crate::{parse_gmsts, EGmstValue, EScale, ETheme, Gmst, GmstViewModel, ModViewModel};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::EModType;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::backup::{Backup, BackupSettings, EDiffLine};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::database::{get_disagreements, get_user_db_dir, load_gmst_dbs};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Diagnostic, EDiagnosticLevel};
 #[cfg(not(target_arch = "wasm32"))]
 use egui::Color32;
 
@@ -14,6 +32,16 @@ pub struct TemplateApp {
     pub theme: ETheme,
     pub scale: EScale,
     pub use_ccr: bool,
+    /// Starfield base directory, detected on first launch
+    pub game_dir: Option<PathBuf>,
+    /// StarfieldCustom.ini path, detected on first launch
+    pub ini_path: Option<PathBuf>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub backup: BackupSettings,
+    /// Named sets of GMST overrides
+    pub profiles: Vec<GmstProfile>,
+    /// The profile the grid belongs to
+    pub active_profile: usize,
 
     // ui
     #[serde(skip)]
@@ -27,15 +55,75 @@ pub struct TemplateApp {
     #[serde(skip)]
     pub default_gmsts: HashMap<String, EGmstValue>,
     #[serde(skip)]
+    pub metadata: HashMap<String, GmstMetadata>,
+    /// The loaded GMST databases, lowest priority first
+    #[serde(skip)]
+    pub gmst_dbs: Vec<GmstDb>,
+    #[serde(skip)]
     pub gmst_vms: Vec<GmstViewModel>,
 
     // runtime
     #[serde(skip)]
     pub search_filter: String,
     #[serde(skip)]
+    pub section_filter: ESectionFilter,
+    /// How the GMST grid is grouped
+    pub grouping: EGmstGrouping,
+    /// The GMST tree of the grouping it was built for
+    #[serde(skip)]
+    pub gmst_groups: Option<(EGmstGrouping, Vec<GmstGroup>)>,
+    #[serde(skip)]
     pub display_edited: bool,
+    /// The new name of the active profile while renaming it
+    #[serde(skip)]
+    pub profile_rename: Option<String>,
+    /// The mod whose GMSTs are edited in the grid instead of the active profile
+    #[serde(skip)]
+    pub editing_mod: Option<ModViewModel>,
+    /// Undo/redo of GMST edits and mod list changes
+    #[serde(skip)]
+    pub history: History,
+    #[serde(skip)]
+    pub show_history: bool,
     #[serde(skip)]
     pub selected_mod: Option<ModViewModel>,
+    #[serde(skip)]
+    pub show_merge: bool,
+    #[serde(skip)]
+    pub show_new_mod: bool,
+    /// File name of the new mod, without extension
+    #[serde(skip)]
+    pub new_mod_name: String,
+    #[serde(skip)]
+    pub new_mod_ccr: bool,
+    #[serde(skip)]
+    pub merge_selection: Vec<PathBuf>,
+    #[serde(skip)]
+    pub merge_name: String,
+    #[serde(skip)]
+    pub show_conflicts: bool,
+    #[serde(skip)]
+    pub conflicts: Option<Vec<GmstConflict>>,
+    #[serde(skip)]
+    pub show_db_report: bool,
+    /// GMSTs whose defaults differ between the databases
+    #[serde(skip)]
+    pub db_disagreements: Option<Vec<GmstDisagreement>>,
+    #[serde(skip)]
+    pub show_log: bool,
+    /// Errors shown in the log panel
+    #[serde(skip)]
+    pub log: Vec<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pub show_backups: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pub backups: Option<Vec<Backup>>,
+    /// The selected backup and its diff against the current file
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pub selected_backup: Option<(Backup, Vec<EDiffLine>)>,
 }
 
 impl Default for TemplateApp {
@@ -45,30 +133,112 @@ impl Default for TemplateApp {
             mods: None,
             ccr_mods: None,
             toasts: Toasts::default(),
-            default_gmsts: parse_gmsts(),
+            default_gmsts: HashMap::default(),
+            metadata: HashMap::default(),
+            gmst_dbs: vec![],
             gmst_vms: vec![],
             search_filter: "".to_owned(),
+            section_filter: ESectionFilter::All,
+            grouping: EGmstGrouping::None,
+            gmst_groups: None,
             display_edited: false,
+            profiles: vec![GmstProfile::new(DEFAULT_PROFILE_NAME)],
+            active_profile: 0,
+            profile_rename: None,
+            editing_mod: None,
+            history: History::default(),
+            show_history: false,
             scale: EScale::Small,
             selected_mod: None,
             use_ccr: false,
+            game_dir: None,
+            ini_path: None,
+            show_merge: false,
+            show_new_mod: false,
+            new_mod_name: String::new(),
+            new_mod_ccr: false,
+            merge_selection: vec![],
+            merge_name: "merged_gmsts".to_owned(),
+            show_conflicts: false,
+            conflicts: None,
+            show_db_report: false,
+            db_disagreements: None,
+            show_log: false,
+            log: vec![],
+            #[cfg(not(target_arch = "wasm32"))]
+            backup: BackupSettings::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            show_backups: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            backups: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            selected_backup: None,
         };
 
-        s.gmst_vms = rebuild_vms(&s.default_gmsts);
+        s.gmst_dbs = load_dbs(&mut s.toasts, &mut s.log);
+        s.metadata = load_gmst_metadata(&mut s.toasts, &mut s.log);
+        let defaults = merge_gmst_dbs(&s.gmst_dbs);
+        s.gmst_vms = rebuild_vms(&defaults, &s.metadata);
+        s.default_gmsts = defaults.values;
 
         s
     }
 }
 
-fn rebuild_vms(default_gmsts: &HashMap<String, EGmstValue>) -> Vec<GmstViewModel> {
+/// Loads the GMST databases, invalid values and broken user databases are reported and skipped
+fn load_dbs(toasts: &mut Toasts, log: &mut Vec<String>) -> Vec<GmstDb> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let dbs = {
+        let (dbs, errors) = load_gmst_dbs();
+        for err in errors {
+            report_error(toasts, log, err);
+        }
+        dbs
+    };
+    #[cfg(target_arch = "wasm32")]
+    let dbs = {
+        let _ = (toasts, log);
+        crate::database::get_embedded_dbs()
+    };
+
+    dbs
+}
+
+/// Loads the GMST metadata, a broken user metadata file is reported and skipped
+fn load_gmst_metadata(toasts: &mut Toasts, log: &mut Vec<String>) -> HashMap<String, GmstMetadata> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let metadata = {
+        let (metadata, err) = crate::metadata::load_metadata();
+        if let Some(err) = err {
+            report_error(toasts, log, err);
+        }
+        metadata
+    };
+    #[cfg(target_arch = "wasm32")]
+    let metadata = {
+        let _ = (toasts, log);
+        crate::metadata::parse_metadata()
+    };
+
+    metadata
+}
+
+fn rebuild_vms(
+    defaults: &crate::database::GmstDefaults,
+    metadata: &HashMap<String, GmstMetadata>,
+) -> Vec<GmstViewModel> {
     let mut list: Vec<GmstViewModel> = vec![];
-    for (name, value) in default_gmsts {
+    for (name, value) in &defaults.values {
         list.push(GmstViewModel {
             gmst: Gmst {
                 name: name.to_owned(),
                 value: value.to_owned(),
             },
+            name: GmstName::parse(name),
             is_edited: false,
+            overlay: None,
+            metadata: metadata.get(name).cloned(),
+            sources: defaults.sources.get(name).cloned().unwrap_or_default(),
         });
     }
     list.sort_by(|a, b| a.gmst.name.cmp(&b.gmst.name));
@@ -84,20 +254,68 @@ impl TemplateApp {
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
-        if let Some(storage) = cc.storage {
-            return eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default();
+        let mut app: TemplateApp = match cc.storage {
+            Some(storage) => eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default(),
+            None => Default::default(),
+        };
+        if app.profiles.is_empty() {
+            app.profiles.push(GmstProfile::new(DEFAULT_PROFILE_NAME));
         }
+        app.active_profile = app.active_profile.min(app.profiles.len() - 1);
+
+        // restore the edits of the last session
+        apply_profile(
+            &app.profiles[app.active_profile],
+            &mut app.gmst_vms,
+            &app.default_gmsts,
+            &mut app.toasts,
+        );
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let app = app.with_detected_paths();
+
+        app
+    }
 
-        Default::default()
+    /// Detects the game directory and ini once at startup, if they are not set
+    /// A stored game directory is only replaced if it no longer has the game, e.g. after a move
+    #[cfg(not(target_arch = "wasm32"))]
+    fn with_detected_paths(mut self) -> Self {
+        use crate::{detect_game_dir, discovery::find_ini_path, get_default_ini_path, is_game_dir};
+
+        if !self.game_dir.as_deref().is_some_and(is_game_dir) {
+            if let Some(dir) = detect_game_dir() {
+                self.game_dir = Some(dir);
+            }
+        }
+        if self.ini_path.is_none() {
+            self.ini_path = find_ini_path(self.game_dir.as_deref()).or_else(get_default_ini_path);
+        }
+        self
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-static BAT_NAME: &str = "my_gmsts";
+const UNDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+const REDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Y);
+const REDO_SHIFT_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::Z,
+);
 
 impl eframe::App for TemplateApp {
     /// Called by the frame work to save state before shutdown.
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        // only the edits are stored, in the active profile
+        // the grid of an edited mod belongs to its file, the profile already has its edits
+        if self.editing_mod.is_none() {
+            if let Some(profile) = self.profiles.get_mut(self.active_profile) {
+                profile.set_gmsts(&self.gmst_vms, &self.default_gmsts);
+            }
+        }
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
@@ -105,24 +323,81 @@ impl eframe::App for TemplateApp {
     #[cfg(target_arch = "wasm32")]
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let Self {
-            theme,
+            theme: _,
             mods: mods_option,
             ccr_mods: ccr_mods_option,
             toasts,
             default_gmsts,
+            metadata: _,
+            gmst_dbs: _,
+            show_db_report: _,
+            db_disagreements: _,
             gmst_vms,
             search_filter,
+            section_filter,
+            grouping,
+            gmst_groups,
             display_edited,
-            scale,
-            selected_mod,
-            use_ccr,
+            profiles,
+            active_profile,
+            profile_rename,
+            editing_mod: _,
+            history,
+            show_history,
+            scale: _,
+            selected_mod: _,
+            use_ccr: _,
+            game_dir: _,
+            ini_path: _,
+            show_merge: _,
+            show_new_mod: _,
+            new_mod_name: _,
+            new_mod_ccr: _,
+            merge_selection: _,
+            merge_name: _,
+            show_conflicts: _,
+            conflicts: _,
+            show_log: _,
+            log: _,
         } = self;
 
         //catppuccin_egui::set_theme(ctx, get_theme(theme));
 
+        handle_history_shortcuts(ctx, history, gmst_vms, mods_option, ccr_mods_option, toasts);
+        show_history_window(
+            ctx,
+            show_history,
+            history,
+            gmst_vms,
+            mods_option,
+            ccr_mods_option,
+            toasts,
+        );
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            show_gmst_list_only(ui, search_filter, display_edited, gmst_vms, default_gmsts);
+            show_profile_bar(
+                ui,
+                profiles,
+                active_profile,
+                profile_rename,
+                gmst_vms,
+                default_gmsts,
+                toasts,
+                history,
+            );
+            show_gmst_list_only(
+                ui,
+                search_filter,
+                section_filter,
+                grouping,
+                gmst_groups,
+                display_edited,
+                gmst_vms,
+                default_gmsts,
+                history,
+            );
         });
+        toasts.show(ctx);
     }
 
     /// Called each time the UI needs repainting, which may be many times per second.
@@ -130,8 +405,9 @@ impl eframe::App for TemplateApp {
     #[cfg(not(target_arch = "wasm32"))]
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         use crate::{
-            add_command_to_ini, get_command_line, get_mod_file_path, get_mods_folder, parse_file,
-            refresh_mods, save_to_file,
+            add_command_to_ini, discovery::find_ini_path, get_command_line, get_conflicts,
+            get_mod_file_path, get_mod_gmsts, get_mods_folder, is_game_dir, refresh_mods,
+            save_to_file, update_mod_file,
         };
 
         let Self {
@@ -140,25 +416,187 @@ impl eframe::App for TemplateApp {
             ccr_mods: ccr_mods_option,
             toasts,
             default_gmsts,
+            metadata,
+            gmst_dbs,
+            show_db_report,
+            db_disagreements,
             gmst_vms,
             search_filter,
+            section_filter,
+            grouping,
+            gmst_groups,
             display_edited,
+            profiles,
+            active_profile,
+            profile_rename,
+            editing_mod,
+            history,
+            show_history,
             scale,
             selected_mod,
             use_ccr,
+            game_dir: game_dir_option,
+            ini_path: ini_path_option,
+            show_merge,
+            show_new_mod,
+            new_mod_name,
+            new_mod_ccr,
+            merge_selection,
+            merge_name,
+            show_conflicts,
+            conflicts: conflicts_option,
+            show_log,
+            log,
+            backup,
+            show_backups,
+            backups: backups_option,
+            selected_backup,
         } = self;
 
         ctx.set_pixels_per_point(f32::from(*scale));
         //catppuccin_egui::set_theme(ctx, get_theme(theme));
 
+        // undo/redo
+        let mut history_changed =
+            handle_history_shortcuts(ctx, history, gmst_vms, mods_option, ccr_mods_option, toasts);
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("🗁 Set game directory").clicked() {
+                        ui.close_menu();
+                        if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                            if is_game_dir(&dir) {
+                                *game_dir_option = Some(dir);
+                                // reload everything from the new directory
+                                *mods_option = None;
+                                *ccr_mods_option = None;
+                                *conflicts_option = None;
+                                *selected_mod = None;
+                                merge_selection.clear();
+                            } else {
+                                report_error(
+                                    toasts,
+                                    log,
+                                    format!("Starfield.exe not found in {}", dir.display()),
+                                );
+                            }
+                        }
+                    }
+                    if ui.button("🗁 Set StarfieldCustom.ini").clicked() {
+                        ui.close_menu();
+                        if let Some(file) = rfd::FileDialog::new()
+                            .add_filter("ini", &["ini"])
+                            .set_file_name("StarfieldCustom.ini")
+                            .pick_file()
+                        {
+                            *ini_path_option = Some(file);
+                            // the load order is read from the ini
+                            *mods_option = None;
+                            *conflicts_option = None;
+                        }
+                    }
+                    if ui.button("🔍 Detect StarfieldCustom.ini").clicked() {
+                        ui.close_menu();
+                        if let Some(file) = find_ini_path(game_dir_option.as_deref()) {
+                            toasts.info(format!("Found {}", file.display()));
+                            *ini_path_option = Some(file);
+                            *mods_option = None;
+                            *conflicts_option = None;
+                        } else {
+                            report_error(toasts, log, "StarfieldCustom.ini not found");
+                        }
+                    }
+                    ui.separator();
+                    if ui.button("🗁 Open GMST databases").clicked() {
+                        ui.close_menu();
+                        if let Some(dir) = get_user_db_dir() {
+                            let result =
+                                std::fs::create_dir_all(&dir).and_then(|_| open::that(&dir));
+                            if let Err(err) = result {
+                                report_error(
+                                    toasts,
+                                    log,
+                                    format!("Could not open folder: {}", err),
+                                );
+                            }
+                        }
+                    }
+                    if ui.button("🔃 Reload GMST databases").clicked() {
+                        ui.close_menu();
+                        *gmst_dbs = load_dbs(toasts, log);
+                        *metadata = load_gmst_metadata(toasts, log);
+                        *db_disagreements = None;
+                        let defaults = merge_gmst_dbs(gmst_dbs);
+                        // keep edited values
+                        let mut vms = rebuild_vms(&defaults, metadata);
+                        for vm in vms.iter_mut() {
+                            if let Some(old) = gmst_vms.iter().find(|o| o.gmst.name == vm.gmst.name)
+                            {
+                                if old.is_edited {
+                                    vm.gmst.value = old.gmst.value.clone();
+                                    vm.overlay = old.overlay.clone();
+                                }
+                            }
+                        }
+                        *gmst_vms = vms;
+                        *gmst_groups = None;
+                        history.clear();
+                        *default_gmsts = defaults.values;
+                        toasts.info("Reloaded GMST databases and metadata");
+                    }
+                    if ui.button("⚖ GMST database report").clicked() {
+                        ui.close_menu();
+                        *show_db_report = true;
+                    }
+                    ui.separator();
+                    if ui.button("🗄 Backups").clicked() {
+                        ui.close_menu();
+                        *show_backups = true;
+                        *backups_option = None;
+                    }
+                    if ui.button("📜 Log").clicked() {
+                        ui.close_menu();
+                        *show_log = true;
+                    }
                     if ui.button("Quit").clicked() {
                         _frame.close();
                     }
                 });
+                ui.menu_button("Edit", |ui| {
+                    let undo_button = egui::Button::new("⮪ Undo")
+                        .shortcut_text(ctx.format_shortcut(&UNDO_SHORTCUT));
+                    if ui.add_enabled(history.can_undo(), undo_button).clicked() {
+                        ui.close_menu();
+                        history_changed |= undo_redo(
+                            false,
+                            history,
+                            gmst_vms,
+                            mods_option,
+                            ccr_mods_option,
+                            toasts,
+                        );
+                    }
+                    let redo_button = egui::Button::new("⮫ Redo")
+                        .shortcut_text(ctx.format_shortcut(&REDO_SHORTCUT));
+                    if ui.add_enabled(history.can_redo(), redo_button).clicked() {
+                        ui.close_menu();
+                        history_changed |= undo_redo(
+                            true,
+                            history,
+                            gmst_vms,
+                            mods_option,
+                            ccr_mods_option,
+                            toasts,
+                        );
+                    }
+                    ui.separator();
+                    if ui.button("🕘 History").clicked() {
+                        ui.close_menu();
+                        *show_history = true;
+                    }
+                });
 
                 // theme button on right
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::RIGHT), |ui| {
@@ -178,210 +616,269 @@ impl eframe::App for TemplateApp {
             });
         });
 
-        if let Ok(cwd) = env::current_dir() {
-            if !cwd.join("Starfield.exe").exists() {
-                // then we are in the wrong dir
-                egui::CentralPanel::default().show(ctx, |ui| {
-                    ui.heading(format!("Starfield GMST editor v{}", VERSION));
-                    ui.hyperlink("https://github.com/rfuzzo/sfgmstenable");
-                    ui.separator();
+        history_changed |= show_history_window(
+            ctx,
+            show_history,
+            history,
+            gmst_vms,
+            mods_option,
+            ccr_mods_option,
+            toasts,
+        );
+        if history_changed {
+            // mod changes can change the conflicts
+            *conflicts_option = None;
+        }
 
-                    ui.heading(format!("⚠ This app needs to be run from the Starfield base directory!\nYou are in {}", cwd.display() ));
-                    ui.separator();
+        let Some(game_dir) = game_dir_option.clone().filter(|p| is_game_dir(p)) else {
+            // then we don't know the game dir
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.heading(format!("Starfield GMST editor v{}", VERSION));
+                ui.hyperlink("https://github.com/rfuzzo/sfgmstenable");
+                ui.separator();
 
-                    show_gmst_list_only(ui, search_filter, display_edited, gmst_vms, default_gmsts );
-                });
-                return;
-            }
-        }
+                ui.heading("⚠ Starfield base directory not found!");
+                if let Some(dir) = game_dir_option.as_ref() {
+                    ui.label(format!("Starfield.exe not found in {}", dir.display()));
+                }
+                if ui.button("🗁 Select folder").clicked() {
+                    if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                        if !is_game_dir(&dir) {
+                            report_error(
+                                toasts,
+                                log,
+                                format!("Starfield.exe not found in {}", dir.display()),
+                            );
+                        }
+                        *game_dir_option = Some(dir);
+                    }
+                }
+                ui.separator();
+
+                show_gmst_list_only(
+                    ui,
+                    search_filter,
+                    section_filter,
+                    grouping,
+                    gmst_groups,
+                    display_edited,
+                    gmst_vms,
+                    default_gmsts,
+                    history,
+                );
+            });
+            toasts.show(ctx);
+            return;
+        };
+
+        let ini_path = ini_path_option.clone();
 
         // fill ist of mods
         // TODO refactor this
         if mods_option.is_none() {
-            *mods_option = Some(refresh_mods(false));
+            *mods_option = Some(
+                refresh_mods(&game_dir, ini_path.as_deref(), false).unwrap_or_else(|err| {
+                    report_error(toasts, log, err);
+                    vec![]
+                }),
+            );
         }
         if ccr_mods_option.is_none() {
-            *ccr_mods_option = Some(refresh_mods(true));
+            *ccr_mods_option = Some(
+                refresh_mods(&game_dir, ini_path.as_deref(), true).unwrap_or_else(|err| {
+                    report_error(toasts, log, err);
+                    vec![]
+                }),
+            );
+        }
+        if conflicts_option.is_none() {
+            if let (Some(mods), Some(ccr_mods)) = (mods_option.as_ref(), ccr_mods_option.as_ref()) {
+                *conflicts_option = Some(get_conflicts(mods, ccr_mods).unwrap_or_else(|err| {
+                    report_error(toasts, log, err);
+                    vec![]
+                }));
+            }
+        }
+        let conflicts = conflicts_option.take().unwrap_or_default();
+        let mut conflicts_changed = false;
+        if editing_mod.is_some() {
+            // editing a mod replaced the values shown from other mods
+            for mod_vm in mods_option
+                .iter_mut()
+                .chain(ccr_mods_option.iter_mut())
+                .flatten()
+            {
+                mod_vm.overlay_enabled = false;
+            }
         }
 
         egui::SidePanel::left("left_panel_id").show(ctx, |ui| {
             // Headers
             ui.heading("GMSTs");
-            // save buttons
-            ui.add_enabled_ui(gmst_vms.iter().any(|p| p.is_edited), |ui| {
+            if let Some(mod_vm) = editing_mod.as_mut() {
+                let is_ccr = mod_vm.mod_type == EModType::CcrMod;
+                let mut close = false;
                 ui.horizontal(|ui| {
-                    let save_path = get_mod_file_path(*use_ccr, BAT_NAME);
+                    ui.label(format!("Editing mod: {}", mod_vm.name))
+                        .on_hover_text(mod_vm.path.display().to_string());
 
-                    // save file
+                    // save to the mod file
                     if ui
                         .button(
-                            egui::RichText::new("🖹 Create command file")
+                            egui::RichText::new("💾 Save mod")
                                 .size(14.0)
                                 .color(Color32::GREEN),
                         )
                         .clicked()
                     {
-                        let map = gmst_vms
-                            .iter()
-                            .filter(|p| p.is_edited)
-                            .map(|p| (p.gmst.name.to_owned(), p.gmst.value))
-                            .collect::<HashMap<String, EGmstValue>>();
-
-                        if save_to_file(toasts, &map, &save_path, *use_ccr) {
-                            // refresh UI
-                            if *use_ccr {
-                                *ccr_mods_option = Some(refresh_mods(*use_ccr));
-                            } else {
-                                *mods_option = Some(refresh_mods(*use_ccr));
-                            }
-
+                        let gmsts = get_mod_gmsts(&mod_vm.gmsts, gmst_vms);
+                        if let Err(err) = update_mod_file(&gmsts, &mod_vm.path, is_ccr, backup) {
+                            report_error(toasts, log, err);
+                        } else {
+                            mod_vm.gmsts = gmsts.into_keys().collect();
+                            conflicts_changed = true;
                             if let Some(selected_mod) = selected_mod {
-                                if selected_mod.path == save_path {
-                                    if let Ok(txt) = std::fs::read_to_string(save_path.clone()) {
-                                        selected_mod.txt = Some(txt);
+                                if selected_mod.path == mod_vm.path {
+                                    if let Err(err) = selected_mod.read_text(default_gmsts) {
+                                        report_error(toasts, log, err);
                                     }
                                 }
                             }
-
-                            toasts.success(format!("Created file: {}", save_path.display()));
+                            toasts.success(format!("Saved {}", mod_vm.path.display()));
                         }
                     }
+                    if ui.button("✖ Close mod").clicked() {
+                        close = true;
+                    }
+                });
+                if close {
+                    // back to the profile
+                    *editing_mod = None;
+                    apply_profile(&profiles[*active_profile], gmst_vms, default_gmsts, toasts);
+                    history.clear();
+                }
+            } else {
+                show_profile_bar(
+                    ui,
+                    profiles,
+                    active_profile,
+                    profile_rename,
+                    gmst_vms,
+                    default_gmsts,
+                    toasts,
+                    history,
+                );
+                // save buttons
+                ui.add_enabled_ui(gmst_vms.iter().any(|p| p.is_edited), |ui| {
+                    ui.horizontal(|ui| {
+                        let file_name = profiles[*active_profile].get_file_name();
+                        let save_path = get_mod_file_path(&game_dir, *use_ccr, &file_name);
 
-                    // append to file
-                    ui.add_enabled_ui(save_path.exists(), |ui| {
+                        // save file
                         if ui
                             .button(
-                                egui::RichText::new("➕ Append to command file")
+                                egui::RichText::new("🖹 Create command file")
                                     .size(14.0)
                                     .color(Color32::GREEN),
                             )
                             .clicked()
                         {
-                            let mut new_gmsts = parse_file(&save_path, *use_ccr);
-                            // add currently edited gmsts
-                            for g in gmst_vms.iter().filter(|p| p.is_edited) {
-                                new_gmsts.insert(g.gmst.name.to_owned(), g.gmst.value);
-                            }
+                            let map = gmst_vms
+                                .iter()
+                                .filter(|p| p.is_edited)
+                                .map(|p| (p.gmst.name.to_owned(), p.gmst.value.clone()))
+                                .collect::<HashMap<String, EGmstValue>>();
+
+                            if let Err(err) = save_to_file(&map, &save_path, *use_ccr, backup) {
+                                report_error(toasts, log, err);
+                            } else {
+                                // refresh UI
+                                if *use_ccr {
+                                    *ccr_mods_option = None;
+                                } else {
+                                    *mods_option = None;
+                                }
+                                conflicts_changed = true;
 
-                            if save_to_file(toasts, &new_gmsts, &save_path, *use_ccr) {
                                 if let Some(selected_mod) = selected_mod {
                                     if selected_mod.path == save_path {
-                                        if let Ok(txt) = std::fs::read_to_string(save_path.clone())
-                                        {
-                                            selected_mod.txt = Some(txt);
+                                        if let Err(err) = selected_mod.read_text(default_gmsts) {
+                                            report_error(toasts, log, err);
                                         }
                                     }
                                 }
 
-                                toasts
-                                    .success(format!("Appended to file: {}", save_path.display()));
+                                toasts.success(format!("Created file: {}", save_path.display()));
                             }
                         }
-                    });
 
-                    // use CCR
-                    ui.checkbox(use_ccr, "Use CCR");
-                });
-            });
+                        // append to file
+                        ui.add_enabled_ui(save_path.exists(), |ui| {
+                            if ui
+                                .button(
+                                    egui::RichText::new("➕ Append to command file")
+                                        .size(14.0)
+                                        .color(Color32::GREEN),
+                                )
+                                .clicked()
+                            {
+                                // only the edited setgs lines change, everything else is kept
+                                let new_gmsts = gmst_vms
+                                    .iter()
+                                    .filter(|p| p.is_edited)
+                                    .map(|p| (p.gmst.name.to_owned(), p.gmst.value.clone()))
+                                    .collect::<HashMap<String, EGmstValue>>();
+
+                                if let Err(err) =
+                                    update_mod_file(&new_gmsts, &save_path, *use_ccr, backup)
+                                {
+                                    report_error(toasts, log, err);
+                                } else {
+                                    conflicts_changed = true;
+                                    if let Some(selected_mod) = selected_mod {
+                                        if selected_mod.path == save_path {
+                                            if let Err(err) = selected_mod.read_text(default_gmsts)
+                                            {
+                                                report_error(toasts, log, err);
+                                            }
+                                        }
+                                    }
 
-            ui.separator();
+                                    toasts.success(format!(
+                                        "Appended to file: {}",
+                                        save_path.display()
+                                    ));
+                                }
+                            }
+                        });
 
-            // search bar
-            ui.horizontal(|ui| {
-                ui.label("Filter: ");
-                ui.text_edit_singleline(search_filter);
-                if ui.button("Clear").clicked() {
-                    *search_filter = "".to_owned();
-                }
+                        // use CCR
+                        ui.checkbox(use_ccr, "Use CCR");
+                    });
+                });
+            }
+            ui.separator();
 
-                let fiter_btn_text = match display_edited {
-                    false => "Show edited",
-                    true => "Show all",
-                };
-                ui.toggle_value(display_edited, fiter_btn_text);
-            });
+            show_search_bar(ui, search_filter, display_edited);
+            show_section_filter(ui, gmst_vms, section_filter, grouping, gmst_groups);
 
             ui.separator();
 
             // main grid
             egui::ScrollArea::horizontal().show(ui, |ui| {
                 egui::ScrollArea::vertical().show(ui, |ui| {
-                    egui::Grid::new("main_grid_id")
-                        .num_columns(3)
-                        .show(ui, |ui| {
-                            // Values
-
-                            for vm in gmst_vms.iter_mut() {
-                                if !search_filter.is_empty()
-                                    && !vm
-                                        .gmst
-                                        .name
-                                        .to_lowercase()
-                                        .contains(&search_filter.to_lowercase())
-                                {
-                                    continue;
-                                }
-
-                                // get values
-                                if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
-                                    vm.is_edited = !default_value.eq(&vm.gmst.value);
-                                }
-
-                                if *display_edited && !vm.is_edited {
-                                    continue;
-                                }
-
-                                if vm.is_edited {
-                                    ui.visuals_mut().override_text_color =
-                                        Some(egui::Color32::GREEN);
-                                } else {
-                                    ui.visuals_mut().override_text_color = None;
-                                }
-
-                                // edited checkbox
-                                ui.add_enabled_ui(false, |ui| {
-                                    ui.checkbox(&mut vm.is_edited, "");
-                                });
-
-                                // mod name
-                                let mut mod_name = vm.gmst.name.to_owned();
-                                if vm.is_edited {
-                                    if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
-                                        mod_name = format!("{} ({})", mod_name, default_value);
-                                    }
-                                }
-                                ui.label(mod_name);
-
-                                // mod value
-                                match vm.gmst.value {
-                                    EGmstValue::Bool(mut b) => {
-                                        ui.checkbox(&mut b, "");
-                                        vm.gmst.value = EGmstValue::Bool(b);
-                                    }
-                                    EGmstValue::Float(mut f) => {
-                                        ui.add(egui::DragValue::new(&mut f).speed(0.1));
-                                        vm.gmst.value = EGmstValue::Float(f);
-                                    }
-                                    EGmstValue::Int(mut i) => {
-                                        ui.add(egui::DragValue::new(&mut i).speed(1));
-                                        vm.gmst.value = EGmstValue::Int(i);
-                                    }
-                                    EGmstValue::UInt(mut u) => {
-                                        ui.add(egui::DragValue::new(&mut u).speed(1));
-                                        vm.gmst.value = EGmstValue::UInt(u);
-                                    }
-                                }
-
-                                // Reset
-                                if vm.is_edited && ui.button("Reset").clicked() {
-                                    if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
-                                        vm.gmst.value = *default_value;
-                                    }
-                                }
-
-                                ui.end_row();
-                            }
-                        });
+                    show_gmst_grid(
+                        ui,
+                        *grouping,
+                        gmst_groups,
+                        gmst_vms,
+                        default_gmsts,
+                        &conflicts,
+                        search_filter,
+                        section_filter,
+                        *display_edited,
+                        history,
+                    );
                 });
             });
         });
@@ -389,30 +886,66 @@ impl eframe::App for TemplateApp {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading(format!("Starfield GMST editor v{}", VERSION));
             ui.hyperlink("https://github.com/rfuzzo/sfgmstenable");
+            ui.label(format!("Game directory: {}", game_dir.display()));
+            match &ini_path {
+                Some(ini_path) => ui.label(format!("Ini: {}", ini_path.display())),
+                None => ui.label("Ini: not set"),
+            };
             ui.separator();
 
             // mods table
             ui.heading("Active mods");
-            ui.label("Change load order by reordering.");
+            ui.label("Change load order by reordering. Other start commands are kept.");
             if let Some(mods) = mods_option {
                 ui.horizontal(|ui| {
                     if ui.button("↻ Refresh").clicked() {
-                        *mods = refresh_mods(false);
+                        match refresh_mods(&game_dir, ini_path.as_deref(), false) {
+                            Ok(refreshed) => *mods = refreshed,
+                            Err(err) => report_error(toasts, log, err),
+                        }
+                        conflicts_changed = true;
                     }
                     if ui.button("🗁 Open folder").clicked() {
-                        if let Err(err) = open::that(get_mods_folder(false)) {
-                            toasts.error(format!("Could not open folder: {}", err));
+                        if let Err(err) = open::that(get_mods_folder(&game_dir, false)) {
+                            report_error(toasts, log, format!("Could not open folder: {}", err));
                         }
                     }
+                    if ui.button("➕ New mod").clicked() {
+                        *show_new_mod = true;
+                        *new_mod_ccr = *use_ccr;
+                    }
+                    if ui.button("🔀 Merge").clicked() {
+                        *show_merge = true;
+                    }
+                    if ui.button("⚠ Conflicts").clicked() {
+                        *show_conflicts = true;
+                    }
                     if ui.button("💾 Save to ini").clicked() {
-                        if let Err(err) = add_command_to_ini(
-                            mods.iter()
-                                .filter(|p| p.enabled)
-                                .map(|p| p.name.to_owned())
-                                .collect::<Vec<_>>()
-                                .as_slice(),
-                        ) {
-                            toasts.error(format!("Failed to save to ini: {}", err));
+                        if let Some(ini_path) = &ini_path {
+                            match add_command_to_ini(
+                                ini_path,
+                                mods.iter()
+                                    .filter_map(|p| p.to_start_command())
+                                    .collect::<Vec<_>>()
+                                    .as_slice(),
+                                backup,
+                            ) {
+                                Ok(warnings) => {
+                                    for warning in warnings {
+                                        toasts.warning(warning);
+                                    }
+                                    toasts.success(format!("Saved {}", ini_path.display()));
+                                }
+                                Err(err) => {
+                                    report_error(toasts, log, err);
+                                }
+                            }
+                        } else {
+                            report_error(
+                                toasts,
+                                log,
+                                "Failed to save to ini: StarfieldCustom.ini not set",
+                            );
                         }
                     }
                 });
@@ -429,6 +962,11 @@ impl eframe::App for TemplateApp {
 
                                     // enabled checkbox
                                     if ui.checkbox(&mut mod_vm.enabled, "").clicked() {
+                                        conflicts_changed = true;
+                                        history.push(EHistoryCommand::SetModEnabled {
+                                            mod_name: mod_vm.name.to_owned(),
+                                            enabled: mod_vm.enabled,
+                                        });
                                         if mod_vm.enabled {
                                             // copy file
                                             toasts.success(format!("{} enabled", mod_vm.name));
@@ -438,30 +976,85 @@ impl eframe::App for TemplateApp {
                                         }
                                     }
 
+                                    // foreign commands
+                                    if mod_vm.mod_type == EModType::Command {
+                                        ui.label(
+                                            egui::RichText::new(format!("⌨ {}", mod_vm.name))
+                                                .italics(),
+                                        )
+                                        .on_hover_text("Console command");
+                                        return;
+                                    }
+
                                     // mod name
                                     ui.label(mod_vm.name.to_owned());
 
                                     // show text
                                     if ui.button("🖹").clicked() {
-                                        if let Ok(txt) = std::fs::read_to_string(&mod_vm.path) {
-                                            mod_vm.txt = Some(txt);
-                                            *selected_mod = Some(mod_vm.to_owned());
+                                        match mod_vm.read_text(default_gmsts) {
+                                            Ok(()) => *selected_mod = Some(mod_vm.to_owned()),
+                                            Err(err) => report_error(toasts, log, err),
+                                        }
+                                    }
+
+                                    // edit mod
+                                    if ui
+                                        .add_enabled(editing_mod.is_none(), egui::Button::new("✏"))
+                                        .on_hover_text("Edit mod")
+                                        .clicked()
+                                    {
+                                        match edit_mod(
+                                            mod_vm,
+                                            false,
+                                            &mut profiles[*active_profile],
+                                            gmst_vms,
+                                            default_gmsts,
+                                            toasts,
+                                        ) {
+                                            Ok(edited) => {
+                                                *editing_mod = Some(edited);
+                                                history.clear();
+                                            }
+                                            Err(err) => report_error(toasts, log, err),
                                         }
                                     }
 
-                                    // toggle show mod values
+                                    // toggle show mod values, not into the values of an edited mod
                                     if ui
-                                        .toggle_value(&mut mod_vm.overlay_enabled, "Toggle show")
+                                        .add_enabled(
+                                            editing_mod.is_none(),
+                                            egui::SelectableLabel::new(
+                                                mod_vm.overlay_enabled,
+                                                "Toggle show",
+                                            ),
+                                        )
+                                        .on_disabled_hover_text("Close the edited mod first")
                                         .clicked()
                                     {
-                                        toggle_mod_values(mod_vm, gmst_vms, default_gmsts, false);
+                                        mod_vm.overlay_enabled = !mod_vm.overlay_enabled;
+                                        if let Err(err) = toggle_mod_values(
+                                            mod_vm,
+                                            gmst_vms,
+                                            default_gmsts,
+                                            false,
+                                            history,
+                                            toasts,
+                                        ) {
+                                            report_error(toasts, log, err);
+                                        }
                                     }
                                 });
                             },
                         );
 
                         if response.is_drag_finished() {
+                            let before = mods.iter().map(|m| m.name.to_owned()).collect();
                             response.update_vec(mods);
+                            let after = mods.iter().map(|m| m.name.to_owned()).collect();
+                            if before != after {
+                                history.push(EHistoryCommand::ReorderMods { before, after });
+                            }
+                            conflicts_changed = true;
                         }
                     });
                 });
@@ -469,8 +1062,7 @@ impl eframe::App for TemplateApp {
                 // start commandline
                 let mut start_command = get_command_line(
                     mods.iter()
-                        .filter(|p| p.enabled)
-                        .map(|p| p.name.to_owned())
+                        .filter_map(|p| p.to_start_command())
                         .collect::<Vec<_>>()
                         .as_slice(),
                 );
@@ -490,11 +1082,15 @@ impl eframe::App for TemplateApp {
             if let Some(ccr_mods) = ccr_mods_option {
                 ui.horizontal(|ui| {
                     if ui.button("↻ Refresh").clicked() {
-                        *ccr_mods = refresh_mods(true);
+                        match refresh_mods(&game_dir, ini_path.as_deref(), true) {
+                            Ok(refreshed) => *ccr_mods = refreshed,
+                            Err(err) => report_error(toasts, log, err),
+                        }
+                        conflicts_changed = true;
                     }
                     if ui.button("🗁 Open folder").clicked() {
-                        if let Err(err) = open::that(get_mods_folder(true)) {
-                            toasts.error(format!("Could not open folder: {}", err));
+                        if let Err(err) = open::that(get_mods_folder(&game_dir, true)) {
+                            report_error(toasts, log, format!("Could not open folder: {}", err));
                         }
                     }
                 });
@@ -511,26 +1107,60 @@ impl eframe::App for TemplateApp {
 
                                         // show text
                                         if ui.button("🖹").clicked() {
-                                            if let Ok(txt) = std::fs::read_to_string(&mod_vm.path) {
-                                                mod_vm.txt = Some(txt);
-                                                *selected_mod = Some(mod_vm.to_owned());
+                                            match mod_vm.read_text(default_gmsts) {
+                                                Ok(()) => *selected_mod = Some(mod_vm.to_owned()),
+                                                Err(err) => report_error(toasts, log, err),
                                             }
                                         }
 
-                                        // toggle show mod values
+                                        // edit mod
                                         if ui
-                                            .toggle_value(
-                                                &mut mod_vm.overlay_enabled,
-                                                "Toggle show",
+                                            .add_enabled(
+                                                editing_mod.is_none(),
+                                                egui::Button::new("✏"),
+                                            )
+                                            .on_hover_text("Edit mod")
+                                            .clicked()
+                                        {
+                                            match edit_mod(
+                                                mod_vm,
+                                                true,
+                                                &mut profiles[*active_profile],
+                                                gmst_vms,
+                                                default_gmsts,
+                                                toasts,
+                                            ) {
+                                                Ok(edited) => {
+                                                    *editing_mod = Some(edited);
+                                                    history.clear();
+                                                }
+                                                Err(err) => report_error(toasts, log, err),
+                                            }
+                                        }
+
+                                        // toggle show mod values, not into the values of an edited mod
+                                        if ui
+                                            .add_enabled(
+                                                editing_mod.is_none(),
+                                                egui::SelectableLabel::new(
+                                                    mod_vm.overlay_enabled,
+                                                    "Toggle show",
+                                                ),
                                             )
+                                            .on_disabled_hover_text("Close the edited mod first")
                                             .clicked()
                                         {
-                                            toggle_mod_values(
+                                            mod_vm.overlay_enabled = !mod_vm.overlay_enabled;
+                                            if let Err(err) = toggle_mod_values(
                                                 mod_vm,
                                                 gmst_vms,
                                                 default_gmsts,
                                                 true,
-                                            );
+                                                history,
+                                                toasts,
+                                            ) {
+                                                report_error(toasts, log, err);
+                                            }
                                         }
                                     });
                                     ui.end_row();
@@ -549,17 +1179,147 @@ impl eframe::App for TemplateApp {
                         .strong()
                         .size(14_f32),
                 );
-                if let Some(mod_text) = selected_mod.txt.to_owned() {
-                    ui.push_id("text_scroll", |ui| {
-                        egui::ScrollArea::vertical().show(ui, |ui| {
-                            ui.add_sized(
-                                ui.available_size(),
-                                egui::TextEdit::multiline(&mut mod_text.as_str()),
-                            );
-                        });
+                if !selected_mod.diagnostics.is_empty() {
+                    let warnings = selected_mod
+                        .diagnostics
+                        .iter()
+                        .filter(|d| d.level == EDiagnosticLevel::Warning)
+                        .count();
+                    ui.label(format!(
+                        "⚠ {} warnings, {} lines not edited by this app",
+                        warnings,
+                        selected_mod.diagnostics.len() - warnings
+                    ));
+                }
+                ui.push_id("text_scroll", |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        show_mod_text(ui, selected_mod);
                     });
+                });
+            }
+        });
+
+        // merge mods
+        if let (Some(mods), Some(ccr_mods)) = (mods_option.as_mut(), ccr_mods_option.as_mut()) {
+            conflicts_changed |= show_merge_window(
+                ctx,
+                show_merge,
+                merge_selection,
+                merge_name,
+                use_ccr,
+                toasts,
+                log,
+                &game_dir,
+                ini_path.as_deref(),
+                backup,
+                mods,
+                ccr_mods,
+            );
+        }
+
+        // backups
+        if show_backups_window(
+            ctx,
+            show_backups,
+            backup,
+            backups_option,
+            selected_backup,
+            toasts,
+            log,
+        ) {
+            // the restored file may be a mod or the ini
+            *mods_option = None;
+            *ccr_mods_option = None;
+            *selected_mod = None;
+            conflicts_changed = true;
+        }
+
+        // conflicts
+        egui::Window::new("⚠ Conflicts")
+            .open(show_conflicts)
+            .show(ctx, |ui| {
+                if ui.button("↻ Refresh").clicked() {
+                    conflicts_changed = true;
+                }
+                ui.separator();
+                show_conflicts_grid(ui, &conflicts);
+            });
+        // new mod
+        if *show_new_mod {
+            let mod_names = mods_option
+                .iter()
+                .chain(ccr_mods_option.iter())
+                .flatten()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>();
+            let edited_count = gmst_vms.iter().filter(|vm| vm.is_edited).count();
+            if show_new_mod_window(
+                ctx,
+                show_new_mod,
+                new_mod_name,
+                new_mod_ccr,
+                &game_dir,
+                &mod_names,
+                edited_count,
+            ) {
+                let path = get_mod_file_path(&game_dir, *new_mod_ccr, new_mod_name);
+                let map = gmst_vms
+                    .iter()
+                    .filter(|p| p.is_edited)
+                    .map(|p| (p.gmst.name.to_owned(), p.gmst.value.clone()))
+                    .collect::<HashMap<String, EGmstValue>>();
+                if let Err(err) = save_to_file(&map, &path, *new_mod_ccr, backup) {
+                    report_error(toasts, log, err);
+                } else {
+                    // show the new mod in the lists
+                    if *new_mod_ccr {
+                        *ccr_mods_option = None;
+                    } else {
+                        *mods_option = None;
+                    }
+                    conflicts_changed = true;
+                    *show_new_mod = false;
+                    new_mod_name.clear();
+                    toasts.success(format!("Created mod: {}", path.display()));
                 }
             }
+        }
+
+        if !conflicts_changed {
+            *conflicts_option = Some(conflicts);
+        }
+
+        // database report
+        if *show_db_report {
+            let disagreements = db_disagreements.get_or_insert_with(|| get_disagreements(gmst_dbs));
+            egui::Window::new("⚖ GMST database report")
+                .open(show_db_report)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "{} GMSTs have different defaults in the databases. The value of the last database is used, override it with a user database.",
+                        disagreements.len()
+                    ));
+                    ui.separator();
+                    show_disagreements_grid(ui, disagreements, search_filter);
+                });
+        }
+
+        // log
+        egui::Window::new("📜 Log").open(show_log).show(ctx, |ui| {
+            if ui.button("Clear").clicked() {
+                log.clear();
+            }
+            ui.separator();
+            if log.is_empty() {
+                ui.label("No errors.");
+            }
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in log.iter() {
+                        ui.label(egui::RichText::new(line).monospace());
+                    }
+                });
         });
 
         // notifications
@@ -567,48 +1327,573 @@ impl eframe::App for TemplateApp {
     }
 }
 
+/// Loads the GMSTs of a mod into the grid, to edit them and save them back to its file
+/// The edits of the grid are kept in the active profile
 #[cfg(not(target_arch = "wasm32"))]
-fn toggle_mod_values(
-    mod_vm: &mut ModViewModel,
-    gmst_vms: &mut [GmstViewModel],
-    default_gmsts: &mut HashMap<String, EGmstValue>,
+fn edit_mod(
+    mod_vm: &ModViewModel,
     is_ccr: bool,
-) {
-    use crate::parse_file;
-
-    if mod_vm.overlay_enabled {
-        let map = parse_file(&mod_vm.path, is_ccr);
-        mod_vm.gmsts = map.iter().map(|f| f.0.to_owned()).collect::<Vec<_>>();
-        for gmst in map {
-            // change values
-            if let Some(val) = gmst_vms.iter_mut().find(|p| p.gmst.name == gmst.0) {
-                val.gmst.value = gmst.1;
-            }
+    profile: &mut GmstProfile,
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    toasts: &mut Toasts,
+) -> crate::error::Result<ModViewModel> {
+    use crate::{parse_file, GmstNameIndex};
+
+    let (map, skipped) = parse_file(&mod_vm.path, is_ccr)?;
+    warn_skipped_lines(toasts, mod_vm, &skipped);
+    profile.set_gmsts(gmst_vms, default_gmsts);
+
+    // the mod is applied like a profile, with the spelling of the databases
+    let names = GmstNameIndex::new(default_gmsts.keys());
+    let mut mod_profile = GmstProfile::new(&mod_vm.name);
+    for (name, value) in map {
+        let name = match names.get(&name) {
+            Some(known) => known.to_owned(),
+            None => name.to_string(),
+        };
+        mod_profile.gmsts.insert(name, value);
+    }
+    apply_profile(&mod_profile, gmst_vms, default_gmsts, toasts);
+
+    let mut edited = mod_vm.to_owned();
+    edited.gmsts = mod_profile.gmsts.into_keys().collect();
+    edited.overlay_enabled = false;
+    Ok(edited)
+}
+
+/// Shows the values of a mod in the grid or reverts them to the defaults, adds the change to the history
+#[cfg(not(target_arch = "wasm32"))]
+fn toggle_mod_values(
+    mod_vm: &mut ModViewModel,
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &mut HashMap<String, EGmstValue>,
+    is_ccr: bool,
+    history: &mut History,
+    toasts: &mut Toasts,
+) -> crate::error::Result<()> {
+    use crate::{history::get_gmst_changes, parse_file};
+    use std::collections::HashSet;
+
+    let before = gmst_vms
+        .iter()
+        .map(|vm| vm.gmst.value.clone())
+        .collect::<Vec<_>>();
+
+    if mod_vm.overlay_enabled {
+        let map = match parse_file(&mod_vm.path, is_ccr) {
+            Ok((map, skipped)) => {
+                warn_skipped_lines(toasts, mod_vm, &skipped);
+                map
+            }
+            Err(err) => {
+                mod_vm.overlay_enabled = false;
+                return Err(err);
+            }
+        };
+        mod_vm.gmsts = map.keys().map(|name| name.to_string()).collect::<Vec<_>>();
+        // change values
+        for vm in gmst_vms.iter_mut() {
+            if let Some(value) = map.get(&vm.name) {
+                vm.gmst.value = value.clone();
+                vm.overlay = Some(value.clone());
+            }
         }
     } else {
         // revert
-        for g in mod_vm.gmsts.iter_mut() {
-            if let Some(val) = gmst_vms.iter_mut().find(|p| p.gmst.name == *g) {
-                if let Some(default_value) = default_gmsts.get(g) {
-                    val.gmst.value = *default_value;
+        let names = mod_vm
+            .gmsts
+            .iter()
+            .map(|name| GmstName::parse(name))
+            .collect::<HashSet<_>>();
+        for vm in gmst_vms.iter_mut().filter(|vm| names.contains(&vm.name)) {
+            if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
+                vm.gmst.value = default_value.clone();
+            }
+            vm.overlay = None;
+        }
+    }
+    history.push(EHistoryCommand::ToggleOverlay {
+        mod_name: mod_vm.name.to_owned(),
+        is_ccr,
+        enabled: mod_vm.overlay_enabled,
+        gmsts: get_gmst_changes(&before, gmst_vms),
+    });
+    Ok(())
+}
+
+/// Warns about the invalid lines of a mod that were skipped
+#[cfg(not(target_arch = "wasm32"))]
+fn warn_skipped_lines(toasts: &mut Toasts, mod_vm: &ModViewModel, skipped: &[Diagnostic]) {
+    if skipped.is_empty() {
+        return;
+    }
+    let message = format!(
+        "Skipped {} invalid lines in {}, see the mod text",
+        skipped.len(),
+        mod_vm.name
+    );
+    log::warn!("{}", message);
+    toasts.warning(message);
+}
+
+/// Shows the dialog for a new mod, returns true if the mod should be created
+#[cfg(not(target_arch = "wasm32"))]
+fn show_new_mod_window(
+    ctx: &egui::Context,
+    show_new_mod: &mut bool,
+    new_mod_name: &mut String,
+    new_mod_ccr: &mut bool,
+    game_dir: &std::path::Path,
+    mod_names: &[&str],
+    edited_count: usize,
+) -> bool {
+    use crate::{get_mod_file_path, get_mod_name_error};
+
+    let mut create = false;
+    let mut cancel = false;
+    egui::Window::new("➕ New mod")
+        .open(show_new_mod)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name: ");
+                ui.add(egui::TextEdit::singleline(new_mod_name).hint_text("fast_ship_combat"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Format: ");
+                ui.radio_value(new_mod_ccr, false, "Bat")
+                    .on_hover_text("A txt file in the game directory, run with bat <name>");
+                ui.radio_value(new_mod_ccr, true, "CCR")
+                    .on_hover_text("A toml file in Data/SFSE/Plugins/ConsoleCommandRunner");
+            });
+
+            let path = get_mod_file_path(game_dir, *new_mod_ccr, new_mod_name);
+            let error = get_mod_name_error(new_mod_name, mod_names.iter().copied()).or_else(|| {
+                path.exists()
+                    .then(|| format!("{} already exists", path.display()))
+            });
+            match &error {
+                Some(error) => ui.colored_label(Color32::RED, error),
+                None => ui.label(format!("File: {}", path.display())),
+            };
+            ui.label(format!(
+                "The mod gets the {} edited GMSTs of the grid.",
+                edited_count
+            ));
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(error.is_none(), egui::Button::new("Create"))
+                    .clicked()
+                {
+                    create = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+    if cancel {
+        *show_new_mod = false;
+    }
+    create
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::too_many_arguments)]
+fn show_merge_window(
+    ctx: &egui::Context,
+    show_merge: &mut bool,
+    merge_selection: &mut Vec<PathBuf>,
+    merge_name: &mut String,
+    use_ccr: &mut bool,
+    toasts: &mut Toasts,
+    log: &mut Vec<String>,
+    game_dir: &std::path::Path,
+    ini_path: Option<&std::path::Path>,
+    backup: &BackupSettings,
+    mods: &mut Vec<ModViewModel>,
+    ccr_mods: &mut Vec<ModViewModel>,
+) -> bool {
+    use crate::{get_load_order, get_mod_file_path, refresh_mods, save_merged_mod};
+
+    let mut merged = false;
+    egui::Window::new("🔀 Merge mods")
+        .open(show_merge)
+        .show(ctx, |ui| {
+            ui.label("Select mods to merge. Later mods overwrite earlier ones.");
+            ui.separator();
+
+            let load_order = get_load_order(mods, ccr_mods);
+            for mod_vm in load_order.iter() {
+                let mut selected = merge_selection.contains(&mod_vm.path);
+                if ui.checkbox(&mut selected, mod_vm.name.to_owned()).changed() {
+                    if selected {
+                        merge_selection.push(mod_vm.path.to_owned());
+                    } else {
+                        merge_selection.retain(|p| *p != mod_vm.path);
+                    }
                 }
             }
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Name: ");
+                ui.text_edit_singleline(merge_name);
+                ui.checkbox(use_ccr, "Use CCR");
+            });
+
+            let save_path = get_mod_file_path(game_dir, *use_ccr, merge_name);
+            let selected = load_order
+                .into_iter()
+                .filter(|p| merge_selection.contains(&p.path))
+                .collect::<Vec<_>>();
+            let is_valid = selected.len() > 1
+                && !merge_name.is_empty()
+                && !selected.iter().any(|p| p.path == save_path);
+
+            ui.add_enabled_ui(is_valid, |ui| {
+                if ui.button("🔀 Merge").clicked() {
+                    if let Err(err) = save_merged_mod(&selected, &save_path, *use_ccr, backup) {
+                        report_error(toasts, log, err);
+                    } else {
+                        toasts.success(format!("Merged into: {}", save_path.display()));
+                        merged = true;
+                    }
+                }
+            });
+        });
+
+    if merged {
+        // refresh UI
+        let refreshed = match *use_ccr {
+            true => refresh_mods(game_dir, ini_path, true).map(|m| *ccr_mods = m),
+            false => refresh_mods(game_dir, ini_path, false).map(|m| *mods = m),
+        };
+        if let Err(err) = refreshed {
+            report_error(toasts, log, err);
         }
     }
+    merged
 }
 
-fn show_gmst_list_only(
+/// Shows the text of a mod, read-only, with its diagnostics next to it
+#[cfg(not(target_arch = "wasm32"))]
+fn show_mod_text(ui: &mut egui::Ui, mod_vm: &ModViewModel) {
+    let Some(txt) = &mod_vm.txt else {
+        return;
+    };
+
+    ui.horizontal_top(|ui| {
+        let text_width = match mod_vm.diagnostics.is_empty() {
+            true => ui.available_width(),
+            false => ui.available_width() * 0.6,
+        };
+        ui.add(
+            egui::TextEdit::multiline(&mut txt.as_str())
+                .code_editor()
+                .desired_width(text_width),
+        );
+
+        ui.vertical(|ui| {
+            for d in &mod_vm.diagnostics {
+                let (icon, color) = match d.level {
+                    EDiagnosticLevel::Warning => ("⚠", Color32::YELLOW),
+                    EDiagnosticLevel::Info => ("ℹ", Color32::GRAY),
+                };
+                ui.colored_label(color, format!("{} {}", icon, d));
+            }
+        });
+    });
+}
+
+/// Shows an error in a toast and adds it to the log panel
+#[cfg(not(target_arch = "wasm32"))]
+fn report_error(toasts: &mut Toasts, log: &mut Vec<String>, err: impl std::fmt::Display) {
+    let message = err.to_string();
+    log::error!("{}", message);
+    log.push(format!(
+        "[{}] {}",
+        chrono::Local::now().format("%H:%M:%S"),
+        message
+    ));
+    toasts.error(message);
+}
+
+/// Shows the backups window, returns true if a backup was restored
+#[cfg(not(target_arch = "wasm32"))]
+fn show_backups_window(
+    ctx: &egui::Context,
+    show_backups: &mut bool,
+    backup: &mut BackupSettings,
+    backups_option: &mut Option<Vec<Backup>>,
+    selected_backup: &mut Option<(Backup, Vec<EDiffLine>)>,
+    toasts: &mut Toasts,
+    log: &mut Vec<String>,
+) -> bool {
+    use crate::backup::{diff_lines, get_backups, restore_backup};
+
+    let mut restored = false;
+    egui::Window::new("🗄 Backups")
+        .open(show_backups)
+        .show(ctx, |ui| {
+            // settings
+            ui.horizontal(|ui| {
+                ui.label(format!("Backup folder: {}", backup.dir.display()));
+                if ui.button("🗁 Change").clicked() {
+                    if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                        backup.dir = dir;
+                        *backups_option = None;
+                        *selected_backup = None;
+                    }
+                }
+                if ui.button("🗁 Open folder").clicked() {
+                    if let Err(err) = open::that(&backup.dir) {
+                        report_error(toasts, log, format!("Could not open folder: {}", err));
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Backups per file: ");
+                ui.add(egui::DragValue::new(&mut backup.retention).clamp_range(0..=100))
+                    .on_hover_text("0 disables backups");
+                if ui.button("↻ Refresh").clicked() {
+                    *backups_option = None;
+                }
+            });
+            ui.separator();
+
+            let backups = backups_option.get_or_insert_with(|| get_backups(backup));
+            if backups.is_empty() {
+                ui.label("No backups found.");
+                return;
+            }
+
+            let mut to_restore = None;
+            ui.push_id("backups_scroll", |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("backups_grid_id")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for b in backups.iter() {
+                                    ui.label(b.time.to_owned());
+                                    ui.label(b.target.display().to_string());
+                                    let is_selected =
+                                        selected_backup.as_ref().is_some_and(|(s, _)| s == b);
+                                    if ui.selectable_label(is_selected, "Diff").clicked() {
+                                        let old =
+                                            std::fs::read_to_string(&b.path).unwrap_or_default();
+                                        let new =
+                                            std::fs::read_to_string(&b.target).unwrap_or_default();
+                                        *selected_backup =
+                                            Some((b.to_owned(), diff_lines(&old, &new)));
+                                    }
+                                    if ui.button("⟲ Restore").clicked() {
+                                        to_restore = Some(b.to_owned());
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+            if let Some(b) = to_restore {
+                match restore_backup(&b, backup) {
+                    Ok(()) => {
+                        toasts.success(format!("Restored {}", b.target.display()));
+                        *backups_option = None;
+                        *selected_backup = None;
+                        restored = true;
+                    }
+                    Err(err) => {
+                        report_error(toasts, log, err);
+                    }
+                }
+            }
+
+            // diff
+            if let Some((b, diff)) = selected_backup {
+                ui.separator();
+                ui.label(format!(
+                    "Changes in {} since {}",
+                    b.target.display(),
+                    b.time
+                ));
+                ui.push_id("backup_diff_scroll", |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        for line in diff.iter() {
+                            let text = match line {
+                                EDiffLine::Same(l) => egui::RichText::new(format!("  {}", l)),
+                                EDiffLine::Added(l) => {
+                                    egui::RichText::new(format!("+ {}", l)).color(Color32::GREEN)
+                                }
+                                EDiffLine::Removed(l) => {
+                                    egui::RichText::new(format!("- {}", l)).color(Color32::RED)
+                                }
+                            };
+                            ui.label(text.monospace());
+                        }
+                    });
+                });
+            }
+        });
+
+    restored
+}
+
+fn get_conflict_text(conflict: &GmstConflict) -> String {
+    let mut text = "Set by:".to_owned();
+    for (mod_name, value) in conflict.values.iter() {
+        text += format!("\n{} ({})", mod_name, value).as_str();
+    }
+    if let Some(winner) = &conflict.winner {
+        text += format!("\nWins: {}", winner).as_str();
+    }
+    text
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn show_conflicts_grid(ui: &mut egui::Ui, conflicts: &[GmstConflict]) {
+    if conflicts.is_empty() {
+        ui.label("No conflicts found.");
+        return;
+    }
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("conflicts_grid_id")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for conflict in conflicts {
+                    ui.label(egui::RichText::new(conflict.name.to_string()).strong());
+                    ui.end_row();
+
+                    for (mod_name, value) in conflict.values.iter() {
+                        let is_winner = conflict.winner.as_ref() == Some(mod_name);
+                        if is_winner {
+                            ui.label(egui::RichText::new("✔").color(Color32::GREEN));
+                        } else {
+                            ui.label("");
+                        }
+                        ui.label(mod_name.to_owned());
+                        ui.label(value.to_string());
+                        ui.end_row();
+                    }
+                }
+            });
+    });
+}
+
+/// Shows the GMSTs whose defaults differ between databases, filtered by name
+#[cfg(not(target_arch = "wasm32"))]
+fn show_disagreements_grid(
     ui: &mut egui::Ui,
-    search_filter: &mut String,
-    display_edited: &mut bool,
-    gmst_vms: &mut [GmstViewModel],
-    default_gmsts: &HashMap<String, EGmstValue>,
+    disagreements: &[GmstDisagreement],
+    search_filter: &str,
 ) {
-    // Headers
-    ui.heading("GMSTs");
-    ui.separator();
+    if disagreements.is_empty() {
+        ui.label("All databases agree.");
+        return;
+    }
+
+    let filter = search_filter.to_lowercase();
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("db_report_grid_id")
+            .num_columns(3)
+            .striped(true)
+            .show(ui, |ui| {
+                for disagreement in disagreements {
+                    if !disagreement.name.to_lowercase().contains(&filter) {
+                        continue;
+                    }
+                    ui.label(egui::RichText::new(disagreement.name.to_owned()).strong());
+                    ui.end_row();
+
+                    for (idx, (source, value)) in disagreement.values.iter().enumerate() {
+                        if idx + 1 == disagreement.values.len() {
+                            ui.label(egui::RichText::new("✔").color(Color32::GREEN))
+                                .on_hover_text("Used");
+                        } else {
+                            ui.label("");
+                        }
+                        ui.label(source.to_string());
+                        ui.label(value.to_string());
+                        ui.end_row();
+                    }
+                }
+            });
+    });
+}
+
+/// Shows the name of a GMST, with its metadata and default source as tooltip
+fn show_gmst_name(ui: &mut egui::Ui, name: String, vm: &GmstViewModel) {
+    let mut lines = vec![];
+    if let Some(metadata) = &vm.metadata {
+        lines.push(metadata.get_tooltip());
+    }
+    if !vm.sources.is_empty() {
+        let sources = vm.sources.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        lines.push(format!("Default from: {}", sources.join(", ")));
+    }
+    let label = ui.label(name);
+    if !lines.is_empty() {
+        label.on_hover_text(lines.join("\n"));
+    }
+}
+
+/// Shows the editor of a GMST value, numbers are clamped to the sane range while they are in it
+/// Returns the response of the editor
+fn show_gmst_value(ui: &mut egui::Ui, vm: &mut GmstViewModel) -> egui::Response {
+    let metadata = vm.metadata.as_ref();
+    let in_range = !matches!(metadata, Some(m) if !m.is_in_range(&vm.gmst.value));
+    let range = match metadata {
+        Some(m) if in_range => m.min.unwrap_or(f64::NEG_INFINITY)..=m.max.unwrap_or(f64::INFINITY),
+        _ => f64::NEG_INFINITY..=f64::INFINITY,
+    };
+
+    ui.horizontal(|ui| {
+        let response = match &mut vm.gmst.value {
+            EGmstValue::Bool(b) => ui.checkbox(b, ""),
+            EGmstValue::Float(f) => ui.add(egui::DragValue::new(f).speed(0.1).clamp_range(range)),
+            EGmstValue::Int(i) => ui.add(egui::DragValue::new(i).speed(1).clamp_range(range)),
+            EGmstValue::UInt(u) => ui.add(egui::DragValue::new(u).speed(1).clamp_range(range)),
+            EGmstValue::String(s) => {
+                // the console can't escape quotes, edits with quotes are rejected
+                let old = s.clone();
+                let response = ui.text_edit_singleline(s);
+                let rejected_id = response.id.with("rejected_quotes");
+                if response.changed() {
+                    let rejected = s.contains('"');
+                    if rejected {
+                        *s = old;
+                    }
+                    ui.data_mut(|d| d.insert_temp(rejected_id, rejected));
+                }
+                if ui.data(|d| d.get_temp::<bool>(rejected_id)) == Some(true) {
+                    ui.colored_label(egui::Color32::RED, "⚠")
+                        .on_hover_text("Values can't contain \", the console can't write them");
+                }
+                response
+            }
+        };
+
+        if !in_range {
+            if let Some(range) = metadata.and_then(|m| m.get_range_text()) {
+                ui.colored_label(egui::Color32::YELLOW, "⚠")
+                    .on_hover_text(format!("Outside of the sane range {}", range));
+            }
+        }
+        response
+    })
+    .inner
+}
 
-    // search bar
+/// Shows the search filter and the edited toggle of the GMST grid
+fn show_search_bar(ui: &mut egui::Ui, search_filter: &mut String, display_edited: &mut bool) {
     ui.horizontal(|ui| {
         ui.label("Filter: ");
         ui.text_edit_singleline(search_filter);
@@ -622,86 +1907,610 @@ fn show_gmst_list_only(
         };
         ui.toggle_value(display_edited, fiter_btn_text);
     });
+}
 
-    ui.separator();
+/// Shows the section filter and grouping of the GMST grid
+fn show_section_filter(
+    ui: &mut egui::Ui,
+    gmst_vms: &[GmstViewModel],
+    section_filter: &mut ESectionFilter,
+    grouping: &mut EGmstGrouping,
+    gmst_groups: &mut Option<(EGmstGrouping, Vec<GmstGroup>)>,
+) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("Section")
+            .selected_text(section_filter.to_string())
+            .show_ui(ui, |ui| {
+                ui.selectable_value(section_filter, ESectionFilter::All, "All");
+                ui.selectable_value(section_filter, ESectionFilter::None, "No section");
+                for section in get_sections(gmst_vms) {
+                    let text = section.to_owned();
+                    ui.selectable_value(section_filter, ESectionFilter::Section(section), text);
+                }
+            });
+        egui::ComboBox::from_label("Group by")
+            .selected_text(grouping.to_string())
+            .show_ui(ui, |ui| {
+                for value in [
+                    EGmstGrouping::None,
+                    EGmstGrouping::Section,
+                    EGmstGrouping::Family,
+                    EGmstGrouping::Category,
+                ] {
+                    ui.selectable_value(grouping, value, value.to_string());
+                }
+            });
 
-    // main grid
-    egui::ScrollArea::vertical().show(ui, |ui| {
-        egui::ScrollArea::horizontal().show(ui, |ui| {
-            egui::Grid::new("main_grid_id")
-                .num_columns(3)
-                .show(ui, |ui| {
-                    // Values
-                    for vm in gmst_vms.iter_mut() {
-                        if !search_filter.is_empty()
-                            && !vm
-                                .gmst
-                                .name
-                                .to_lowercase()
-                                .contains(&search_filter.to_lowercase())
-                        {
-                            continue;
-                        }
+        if *grouping != EGmstGrouping::None {
+            let groups = get_gmst_groups(gmst_vms, *grouping, gmst_groups);
+            if ui.button("⊞ Expand all").clicked() {
+                set_groups_open(ui.ctx(), *grouping, groups, "", true);
+            }
+            if ui.button("⊟ Collapse all").clicked() {
+                set_groups_open(ui.ctx(), *grouping, groups, "", false);
+            }
+        }
+    });
+}
 
-                        // get values
-                        if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
-                            vm.is_edited = !default_value.eq(&vm.gmst.value);
-                        }
+/// Gets all sections of the GMSTs, sorted
+fn get_sections(gmst_vms: &[GmstViewModel]) -> Vec<String> {
+    let mut sections = gmst_vms
+        .iter()
+        .filter_map(|vm| vm.name.section.to_owned())
+        .collect::<Vec<_>>();
+    sections.sort_by_key(|s| s.to_lowercase());
+    sections.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+    sections
+}
 
-                        if *display_edited && !vm.is_edited {
-                            continue;
-                        }
+/// Gets the GMST tree, rebuilds it if the grouping changed
+fn get_gmst_groups<'a>(
+    gmst_vms: &[GmstViewModel],
+    grouping: EGmstGrouping,
+    gmst_groups: &'a mut Option<(EGmstGrouping, Vec<GmstGroup>)>,
+) -> &'a [GmstGroup] {
+    if !matches!(gmst_groups, Some((g, _)) if *g == grouping) {
+        *gmst_groups = Some((grouping, group_gmsts(gmst_vms, grouping)));
+    }
+    match gmst_groups {
+        Some((_, groups)) => groups,
+        None => &[],
+    }
+}
 
-                        if vm.is_edited {
-                            ui.visuals_mut().override_text_color = Some(egui::Color32::GREEN);
-                        } else {
-                            ui.visuals_mut().override_text_color = None;
-                        }
+/// The id of a group header, independent of the ui so all groups can be opened at once
+fn get_group_id(grouping: EGmstGrouping, path: &str) -> egui::Id {
+    egui::Id::new(("gmst_group", grouping, path))
+}
 
-                        // edited checkbox
-                        ui.add_enabled_ui(false, |ui| {
-                            ui.checkbox(&mut vm.is_edited, "");
-                        });
+/// Opens or closes the groups and their children
+fn set_groups_open(
+    ctx: &egui::Context,
+    grouping: EGmstGrouping,
+    groups: &[GmstGroup],
+    parent: &str,
+    open: bool,
+) {
+    for group in groups {
+        let path = format!("{}/{}", parent, group.name);
+        let mut state =
+            CollapsingState::load_with_default_open(ctx, get_group_id(grouping, &path), false);
+        state.set_open(open);
+        state.store(ctx);
+        set_groups_open(ctx, grouping, &group.children, &path, open);
+    }
+}
 
-                        // mod name
-                        let mut mod_name = vm.gmst.name.to_owned();
-                        if vm.is_edited {
-                            if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
-                                mod_name = format!("{} ({})", mod_name, default_value);
-                            }
-                        }
-                        ui.label(mod_name);
+/// Checks if a GMST is shown with the current filters
+fn is_gmst_visible(
+    vm: &GmstViewModel,
+    search_filter: &str,
+    section_filter: &ESectionFilter,
+    display_edited: bool,
+) -> bool {
+    if !section_filter.matches(&vm.name) {
+        return false;
+    }
+    if !search_filter.is_empty()
+        && !vm
+            .gmst
+            .name
+            .to_lowercase()
+            .contains(&search_filter.to_lowercase())
+    {
+        return false;
+    }
+    !display_edited || vm.is_edited
+}
 
-                        // mod value
-                        match vm.gmst.value {
-                            EGmstValue::Bool(mut b) => {
-                                ui.checkbox(&mut b, "");
-                                vm.gmst.value = EGmstValue::Bool(b);
-                            }
-                            EGmstValue::Float(mut f) => {
-                                ui.add(egui::DragValue::new(&mut f).speed(0.1));
-                                vm.gmst.value = EGmstValue::Float(f);
-                            }
-                            EGmstValue::Int(mut i) => {
-                                ui.add(egui::DragValue::new(&mut i).speed(1));
-                                vm.gmst.value = EGmstValue::Int(i);
-                            }
-                            EGmstValue::UInt(mut u) => {
-                                ui.add(egui::DragValue::new(&mut u).speed(1));
-                                vm.gmst.value = EGmstValue::UInt(u);
-                            }
-                        }
+/// Shows the GMST grid, as a collapsible tree if grouped
+#[allow(clippy::too_many_arguments)]
+fn show_gmst_grid(
+    ui: &mut egui::Ui,
+    grouping: EGmstGrouping,
+    gmst_groups: &mut Option<(EGmstGrouping, Vec<GmstGroup>)>,
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    conflicts: &[GmstConflict],
+    search_filter: &str,
+    section_filter: &ESectionFilter,
+    display_edited: bool,
+    history: &mut History,
+) {
+    // get values
+    for vm in gmst_vms.iter_mut() {
+        if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
+            vm.is_edited = !default_value.eq(&vm.gmst.value);
+        }
+    }
+    let visible = gmst_vms
+        .iter()
+        .map(|vm| is_gmst_visible(vm, search_filter, section_filter, display_edited))
+        .collect::<Vec<_>>();
+
+    let groups = get_gmst_groups(gmst_vms, grouping, gmst_groups);
+    if grouping == EGmstGrouping::None {
+        for group in groups {
+            show_gmst_rows(
+                ui,
+                &group.gmsts,
+                &visible,
+                gmst_vms,
+                default_gmsts,
+                conflicts,
+                history,
+            );
+        }
+        return;
+    }
+    for group in groups {
+        show_gmst_group(
+            ui,
+            grouping,
+            group,
+            "",
+            &visible,
+            gmst_vms,
+            default_gmsts,
+            conflicts,
+            history,
+        );
+    }
+}
 
-                        // Reset
-                        if vm.is_edited && ui.button("Reset").clicked() {
-                            if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
-                                vm.gmst.value = *default_value;
-                            }
-                        }
+/// Shows a collapsible group with its edited count, groups without visible GMSTs are hidden
+#[allow(clippy::too_many_arguments)]
+fn show_gmst_group(
+    ui: &mut egui::Ui,
+    grouping: EGmstGrouping,
+    group: &GmstGroup,
+    parent: &str,
+    visible: &[bool],
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    conflicts: &[GmstConflict],
+    history: &mut History,
+) {
+    let all_gmsts = group.get_all_gmsts();
+    let count = all_gmsts.iter().filter(|idx| visible[**idx]).count();
+    if count == 0 {
+        return;
+    }
+    let edited = all_gmsts
+        .iter()
+        .filter(|idx| gmst_vms[**idx].is_edited)
+        .count();
+
+    let path = format!("{}/{}", parent, group.name);
+    let id = get_group_id(grouping, &path);
+    CollapsingState::load_with_default_open(ui.ctx(), id, false)
+        .show_header(ui, |ui| {
+            ui.label(egui::RichText::new(&group.name).strong());
+            ui.label(format!("({})", count));
+            let text = format!("{} edited", edited);
+            if edited > 0 {
+                ui.colored_label(egui::Color32::GREEN, text);
+            } else {
+                ui.weak(text);
+            }
+        })
+        .body(|ui| {
+            for child in &group.children {
+                show_gmst_group(
+                    ui,
+                    grouping,
+                    child,
+                    &path,
+                    visible,
+                    gmst_vms,
+                    default_gmsts,
+                    conflicts,
+                    history,
+                );
+            }
+            if group.gmsts.iter().any(|idx| visible[*idx]) {
+                ui.push_id(id, |ui| {
+                    show_gmst_rows(
+                        ui,
+                        &group.gmsts,
+                        visible,
+                        gmst_vms,
+                        default_gmsts,
+                        conflicts,
+                        history,
+                    );
+                });
+            }
+        });
+}
 
-                        ui.end_row();
+/// Shows a grid of the visible GMSTs, edits and resets are added to the history
+fn show_gmst_rows(
+    ui: &mut egui::Ui,
+    gmsts: &[usize],
+    visible: &[bool],
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    conflicts: &[GmstConflict],
+    history: &mut History,
+) {
+    egui::Grid::new("main_grid_id")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for idx in gmsts {
+                if !visible[*idx] {
+                    continue;
+                }
+                let vm = &mut gmst_vms[*idx];
+
+                if vm.is_edited {
+                    ui.visuals_mut().override_text_color = Some(egui::Color32::GREEN);
+                } else {
+                    ui.visuals_mut().override_text_color = None;
+                }
+
+                // edited checkbox
+                ui.add_enabled_ui(false, |ui| {
+                    ui.checkbox(&mut vm.is_edited, "");
+                });
+
+                // mod name
+                let mut mod_name = vm.gmst.name.to_owned();
+                if vm.is_edited {
+                    if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
+                        mod_name = format!("{} ({})", mod_name, default_value);
+                    }
+                }
+                ui.horizontal(|ui| {
+                    show_gmst_name(ui, mod_name, vm);
+                    let name = GmstName::parse(&vm.gmst.name);
+                    if let Ok(idx) = conflicts.binary_search_by(|c| c.name.cmp(&name)) {
+                        ui.label("⚠")
+                            .on_hover_text(get_conflict_text(&conflicts[idx]));
                     }
                 });
+
+                // mod value
+                let old_value = vm.gmst.value.clone();
+                let response = show_gmst_value(ui, vm);
+                if vm.gmst.value != old_value {
+                    history.push_edit(&vm.gmst.name, old_value, vm.gmst.value.clone());
+                }
+                // dragging or typing continues the edit
+                if history.is_editing(&vm.gmst.name) && !response.dragged() && !response.has_focus()
+                {
+                    history.end_edit();
+                }
+
+                // Reset
+                if vm.is_edited && ui.button("Reset").clicked() {
+                    if let Some(default_value) = default_gmsts.get(&vm.gmst.name) {
+                        history.push(EHistoryCommand::SetGmsts(vec![GmstChange {
+                            name: vm.gmst.name.to_owned(),
+                            old: vm.gmst.value.clone(),
+                            new: default_value.clone(),
+                        }]));
+                        vm.gmst.value = default_value.clone();
+                    }
+                }
+
+                ui.end_row();
+            }
+        });
+    ui.visuals_mut().override_text_color = None;
+}
+
+/// Undoes or redoes the last change and shows it in a toast, returns true if there was one
+fn undo_redo(
+    redo: bool,
+    history: &mut History,
+    gmst_vms: &mut [GmstViewModel],
+    mods: &mut Option<Vec<ModViewModel>>,
+    ccr_mods: &mut Option<Vec<ModViewModel>>,
+    toasts: &mut Toasts,
+) -> bool {
+    let command = match redo {
+        true => history.redo(gmst_vms, mods.as_mut(), ccr_mods.as_mut()),
+        false => history.undo(gmst_vms, mods.as_mut(), ccr_mods.as_mut()),
+    };
+    let Some(command) = command else {
+        return false;
+    };
+    let verb = if redo { "Redo" } else { "Undo" };
+    toasts.info(format!("{}: {}", verb, command.get_description()));
+    true
+}
+
+/// Handles Ctrl+Z, Ctrl+Y and Ctrl+Shift+Z, unless a text field uses them
+/// Returns true if a change was undone or redone
+fn handle_history_shortcuts(
+    ctx: &egui::Context,
+    history: &mut History,
+    gmst_vms: &mut [GmstViewModel],
+    mods: &mut Option<Vec<ModViewModel>>,
+    ccr_mods: &mut Option<Vec<ModViewModel>>,
+    toasts: &mut Toasts,
+) -> bool {
+    if ctx.wants_keyboard_input() {
+        return false;
+    }
+    let (undo, redo) = ctx.input_mut(|i| {
+        let undo = i.consume_shortcut(&UNDO_SHORTCUT);
+        let redo = i.consume_shortcut(&REDO_SHORTCUT) || i.consume_shortcut(&REDO_SHIFT_SHORTCUT);
+        (undo, redo)
+    });
+    let mut changed = false;
+    if undo {
+        changed |= undo_redo(false, history, gmst_vms, mods, ccr_mods, toasts);
+    }
+    if redo {
+        changed |= undo_redo(true, history, gmst_vms, mods, ccr_mods, toasts);
+    }
+    changed
+}
+
+/// Shows the changes that can be undone and redone, clicking one undoes or redoes up to it
+/// Returns true if a change was undone or redone
+fn show_history_window(
+    ctx: &egui::Context,
+    show_history: &mut bool,
+    history: &mut History,
+    gmst_vms: &mut [GmstViewModel],
+    mods: &mut Option<Vec<ModViewModel>>,
+    ccr_mods: &mut Option<Vec<ModViewModel>>,
+    toasts: &mut Toasts,
+) -> bool {
+    let mut undo_count = 0;
+    let mut redo_count = 0;
+    egui::Window::new("🕘 History")
+        .open(show_history)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(history.can_undo(), egui::Button::new("⮪ Undo"))
+                    .clicked()
+                {
+                    undo_count = 1;
+                }
+                if ui
+                    .add_enabled(history.can_redo(), egui::Button::new("⮫ Redo"))
+                    .clicked()
+                {
+                    redo_count = 1;
+                }
+                if ui.button("Clear").clicked() {
+                    history.clear();
+                }
+            });
+            ui.separator();
+            if !history.can_undo() && !history.can_redo() {
+                ui.label("No changes.");
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let commands = history.get_undo_commands();
+                for (i, command) in commands.iter().enumerate() {
+                    // the last change is the current state
+                    let is_current = i + 1 == commands.len();
+                    if ui
+                        .selectable_label(is_current, command.get_description())
+                        .clicked()
+                    {
+                        undo_count = commands.len() - i - 1;
+                    }
+                }
+                for (i, command) in history.get_redo_commands().enumerate() {
+                    if ui
+                        .add(
+                            egui::Label::new(egui::RichText::new(command.get_description()).weak())
+                                .sense(egui::Sense::click()),
+                        )
+                        .clicked()
+                    {
+                        redo_count = i + 1;
+                    }
+                }
+            });
+        });
+
+    let mut changed = false;
+    for _ in 0..undo_count {
+        changed |= undo_redo(false, history, gmst_vms, mods, ccr_mods, toasts);
+    }
+    for _ in 0..redo_count {
+        changed |= undo_redo(true, history, gmst_vms, mods, ccr_mods, toasts);
+    }
+    changed
+}
+
+/// Switches the grid to another profile, the edits are kept in the profile that is left
+fn switch_profile(
+    profiles: &mut [GmstProfile],
+    active_profile: &mut usize,
+    idx: usize,
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    toasts: &mut Toasts,
+) {
+    if let Some(profile) = profiles.get_mut(*active_profile) {
+        profile.set_gmsts(gmst_vms, default_gmsts);
+    }
+    *active_profile = idx;
+    apply_profile(&profiles[idx], gmst_vms, default_gmsts, toasts);
+}
+
+/// Sets the grid to a profile, warns about GMSTs that are not in the databases
+fn apply_profile(
+    profile: &GmstProfile,
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    toasts: &mut Toasts,
+) {
+    let missing = profile.apply(gmst_vms, default_gmsts);
+    if !missing.is_empty() {
+        let message = format!(
+            "Unknown GMSTs in profile {}: {}",
+            profile.name,
+            missing.join(", ")
+        );
+        log::warn!("{}", message);
+        toasts.warning(message);
+    }
+}
+
+/// Shows the profile selector and the buttons to add, duplicate, rename and delete profiles
+/// The history is cleared when the grid is switched to another profile
+#[allow(clippy::too_many_arguments)]
+fn show_profile_bar(
+    ui: &mut egui::Ui,
+    profiles: &mut Vec<GmstProfile>,
+    active_profile: &mut usize,
+    profile_rename: &mut Option<String>,
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    toasts: &mut Toasts,
+    history: &mut History,
+) {
+    let profile_count = profiles.len();
+    let profile = *active_profile;
+    ui.horizontal(|ui| {
+        if let Some(name) = profile_rename {
+            ui.label("Profile: ");
+            let response = ui.text_edit_singleline(name);
+            let others = profiles
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != *active_profile)
+                .map(|(_, p)| p.clone())
+                .collect::<Vec<_>>();
+            let is_taken = is_profile_name_taken(&others, name);
+            let is_valid = !name.trim().is_empty() && !is_taken;
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.add_enabled(is_valid, egui::Button::new("✔")).clicked() || (submitted && is_valid)
+            {
+                profiles[*active_profile].name = name.trim().to_owned();
+                *profile_rename = None;
+            } else if ui.button("✖").clicked() {
+                *profile_rename = None;
+            } else if is_taken {
+                ui.colored_label(egui::Color32::RED, "Name is taken");
+            }
+            return;
+        }
+
+        let mut selected = *active_profile;
+        egui::ComboBox::from_label("Profile")
+            .selected_text(profiles[*active_profile].name.as_str())
+            .show_ui(ui, |ui| {
+                for (i, profile) in profiles.iter().enumerate() {
+                    ui.selectable_value(&mut selected, i, profile.name.as_str());
+                }
+            });
+        if selected != *active_profile {
+            switch_profile(
+                profiles,
+                active_profile,
+                selected,
+                gmst_vms,
+                default_gmsts,
+                toasts,
+            );
+        }
+
+        if ui.button("➕").on_hover_text("New profile").clicked() {
+            let name = get_unique_profile_name(profiles, "New profile");
+            profiles.push(GmstProfile::new(&name));
+            let idx = profiles.len() - 1;
+            switch_profile(
+                profiles,
+                active_profile,
+                idx,
+                gmst_vms,
+                default_gmsts,
+                toasts,
+            );
+        }
+        if ui.button("⎘").on_hover_text("Duplicate profile").clicked() {
+            profiles[*active_profile].set_gmsts(gmst_vms, default_gmsts);
+            let mut profile = profiles[*active_profile].clone();
+            profile.name = get_unique_profile_name(profiles, &profile.name);
+            profiles.push(profile);
+            *active_profile = profiles.len() - 1;
+        }
+        if ui.button("✏").on_hover_text("Rename profile").clicked() {
+            *profile_rename = Some(profiles[*active_profile].name.to_owned());
+        }
+        if ui
+            .add_enabled(profiles.len() > 1, egui::Button::new("🗑"))
+            .on_hover_text("Delete profile")
+            .clicked()
+        {
+            profiles.remove(*active_profile);
+            *active_profile = active_profile.saturating_sub(1);
+            apply_profile(&profiles[*active_profile], gmst_vms, default_gmsts, toasts);
+        }
+    });
+    if *active_profile != profile || profiles.len() != profile_count {
+        history.clear();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn show_gmst_list_only(
+    ui: &mut egui::Ui,
+    search_filter: &mut String,
+    section_filter: &mut ESectionFilter,
+    grouping: &mut EGmstGrouping,
+    gmst_groups: &mut Option<(EGmstGrouping, Vec<GmstGroup>)>,
+    display_edited: &mut bool,
+    gmst_vms: &mut [GmstViewModel],
+    default_gmsts: &HashMap<String, EGmstValue>,
+    history: &mut History,
+) {
+    // Headers
+    ui.heading("GMSTs");
+    ui.separator();
+
+    show_search_bar(ui, search_filter, display_edited);
+    show_section_filter(ui, gmst_vms, section_filter, grouping, gmst_groups);
+
+    ui.separator();
+
+    // main grid
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            show_gmst_grid(
+                ui,
+                *grouping,
+                gmst_groups,
+                gmst_vms,
+                default_gmsts,
+                &[],
+                search_filter,
+                section_filter,
+                *display_edited,
+                history,
+            );
         });
     });
 }
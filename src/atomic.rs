@@ -0,0 +1,140 @@
+//! Atomic file writes
+//!
+//! Files are written to a temp file in the same directory, synced to disk and then renamed
+//! over the target, so a crash never leaves a half-written mod or ini behind.
+
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// The step of writing a file that failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EWriteStage {
+    Read,
+    Serialize,
+    Backup,
+    CreateDir,
+    CreateTemp,
+    Write,
+    Sync,
+    Rename,
+}
+
+impl Display for EWriteStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            EWriteStage::Read => "read",
+            EWriteStage::Serialize => "serialize",
+            EWriteStage::Backup => "back up",
+            EWriteStage::CreateDir => "create folder for",
+            EWriteStage::CreateTemp => "create temp file for",
+            EWriteStage::Write => "write",
+            EWriteStage::Sync => "sync",
+            EWriteStage::Rename => "replace",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug)]
+pub struct WriteError {
+    /// The file that should have been written
+    pub path: PathBuf,
+    pub stage: EWriteStage,
+    pub source: io::Error,
+}
+
+impl WriteError {
+    pub fn new(path: &Path, stage: EWriteStage, source: io::Error) -> Self {
+        WriteError {
+            path: path.to_owned(),
+            stage,
+            source,
+        }
+    }
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to {} {}: {}",
+            self.stage,
+            self.path.display(),
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Gets the temp file path next to a file
+fn get_temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Writes a file atomically, the target is either fully replaced or left untouched
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), WriteError> {
+    let err = |stage| move |e| WriteError::new(path, stage, e);
+
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        fs::create_dir_all(parent).map_err(err(EWriteStage::CreateDir))?;
+    }
+
+    let temp_path = get_temp_path(path);
+    let result = (|| {
+        let mut file = File::create(&temp_path).map_err(err(EWriteStage::CreateTemp))?;
+        file.write_all(contents).map_err(err(EWriteStage::Write))?;
+        file.sync_all().map_err(err(EWriteStage::Sync))?;
+        drop(file);
+        fs::rename(&temp_path, path).map_err(err(EWriteStage::Rename))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // persist the rename itself
+    #[cfg(unix)]
+    if let Some(parent) = parent {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn replaces_file_without_temp_files() {
+        let root = TempDir::new("atomic");
+        let path = root.join("sub").join("my_gmsts.txt");
+
+        write_atomic(&path, b"setgs a 1\n").unwrap();
+        write_atomic(&path, b"setgs a 2\n").unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let files = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        let err = write_atomic(&root.join("sub"), b"").unwrap_err();
+
+        assert_eq!(contents, "setgs a 2\n");
+        assert_eq!(files, 1);
+        assert_eq!(err.stage, EWriteStage::Rename);
+    }
+}
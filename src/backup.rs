@@ -0,0 +1,351 @@
+//! Timestamped backups of every file the editor overwrites
+//!
+//! Each target file gets its own folder in the backup directory, containing
+//! the original path in `origin.txt` and one `<timestamp>.bak` file per backup.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::atomic::{write_atomic, EWriteStage, WriteError};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+const ORIGIN_FILE: &str = "origin.txt";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub dir: PathBuf,
+    /// How many backups are kept per file, 0 disables backups
+    pub retention: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        let dir = ProjectDirs::from("", "", "sf_gmst_editor")
+            .map(|p| p.data_dir().join("backups"))
+            .unwrap_or(PathBuf::from("backups"));
+        BackupSettings { dir, retention: 10 }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Backup {
+    /// The file that was backed up
+    pub target: PathBuf,
+    /// The backup file
+    pub path: PathBuf,
+    /// Human readable time of the backup
+    pub time: String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum EDiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// FNV-1a, stable across Rust versions unlike DefaultHasher
+fn hash_path(path: &Path) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in path.to_string_lossy().bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+/// Gets the backup folder of a file
+fn get_target_dir(target: &Path, settings: &BackupSettings) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    settings
+        .dir
+        .join(format!("{}-{:08x}", name, hash_path(target)))
+}
+
+/// Copies a file to the backup directory before it is overwritten
+/// Returns the backup path, or None if the file doesn't exist yet
+pub fn backup_file(target: &Path, settings: &BackupSettings) -> io::Result<Option<PathBuf>> {
+    if settings.retention == 0 || !target.is_file() {
+        return Ok(None);
+    }
+
+    // canonical paths are \\?\ paths on Windows, they are only used for the folder name
+    let origin_path = match target.is_absolute() {
+        true => target.to_owned(),
+        false => std::env::current_dir()?.join(target),
+    };
+    let target = fs::canonicalize(target)?;
+    let target_dir = get_target_dir(&target, settings);
+    fs::create_dir_all(&target_dir)?;
+    let origin = target_dir.join(ORIGIN_FILE);
+    if !origin.exists() {
+        let mut file = fs::File::create(origin)?;
+        write!(file, "{}", origin_path.display())?;
+    }
+
+    let timestamp = chrono::Local::now().format(TIMESTAMP_FORMAT).to_string();
+    let mut backup = target_dir.join(format!("{}.bak", timestamp));
+    let mut cnt = 1;
+    while backup.exists() {
+        backup = target_dir.join(format!("{}_{}.bak", timestamp, cnt));
+        cnt += 1;
+    }
+    fs::copy(&target, &backup)?;
+
+    prune_backups(&target_dir, settings.retention)?;
+    Ok(Some(backup))
+}
+
+/// Gets all backups in a target folder, newest first
+fn get_target_backups(target_dir: &Path) -> Vec<PathBuf> {
+    let mut backups = fs::read_dir(target_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "bak"))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// Deletes all but the newest backups of a target
+fn prune_backups(target_dir: &Path, retention: usize) -> io::Result<()> {
+    for old in get_target_backups(target_dir).into_iter().skip(retention) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Gets all backups in the backup directory, newest first
+pub fn get_backups(settings: &BackupSettings) -> Vec<Backup> {
+    let mut backups = vec![];
+    let Ok(entries) = fs::read_dir(&settings.dir) else {
+        return backups;
+    };
+
+    for target_dir in entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()) {
+        let Ok(origin) = fs::read_to_string(target_dir.join(ORIGIN_FILE)) else {
+            continue;
+        };
+        for path in get_target_backups(&target_dir) {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let time = chrono::NaiveDateTime::parse_from_str(
+                stem.split('_').next().unwrap_or_default(),
+                TIMESTAMP_FORMAT,
+            )
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or(stem);
+            backups.push(Backup {
+                target: PathBuf::from(origin.trim()),
+                path,
+                time,
+            });
+        }
+    }
+
+    backups.sort_by(|a, b| b.path.file_name().cmp(&a.path.file_name()));
+    backups
+}
+
+/// Restores a backup, the current file is backed up first
+pub fn restore_backup(backup: &Backup, settings: &BackupSettings) -> Result<(), WriteError> {
+    let contents = fs::read(&backup.path)
+        .map_err(|err| WriteError::new(&backup.path, EWriteStage::Read, err))?;
+    backup_file(&backup.target, settings)
+        .map_err(|err| WriteError::new(&backup.target, EWriteStage::Backup, err))?;
+    write_atomic(&backup.target, &contents)
+}
+
+/// Line based diff from old to new text
+pub fn diff_lines(old: &str, new: &str) -> Vec<EDiffLine> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let mut diff = vec![];
+    diff_range(&old, &new, &mut diff);
+    diff
+}
+
+/// Diffs two ranges of lines around the middle snake of Myers' algorithm, in linear space
+fn diff_range(old: &[&str], new: &[&str], diff: &mut Vec<EDiffLine>) {
+    let same = |lines: &[&str]| {
+        lines
+            .iter()
+            .map(|l| EDiffLine::Same((*l).to_owned()))
+            .collect::<Vec<_>>()
+    };
+
+    // backups mostly differ in a few lines
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    diff.extend(same(&old[..prefix]));
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    if old_middle.is_empty() {
+        diff.extend(new_middle.iter().map(|l| EDiffLine::Added((*l).to_owned())));
+    } else if new_middle.is_empty() {
+        diff.extend(
+            old_middle
+                .iter()
+                .map(|l| EDiffLine::Removed((*l).to_owned())),
+        );
+    } else {
+        let (x, y, u, v) = find_middle_snake(old_middle, new_middle);
+        diff_range(&old_middle[..x], &new_middle[..y], diff);
+        diff.extend(same(&old_middle[x..u]));
+        diff_range(&old_middle[u..], &new_middle[v..], diff);
+    }
+
+    diff.extend(same(&old[old.len() - suffix..]));
+}
+
+/// Finds the snake in the middle of a shortest edit script, returns its start and end
+/// The ranges must differ in their first and last lines
+fn find_middle_snake(old: &[&str], new: &[&str]) -> (usize, usize, usize, usize) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    // furthest x on each diagonal k = x - y, from the start and from the end
+    let offset = max + 1;
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+    let idx = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[idx(k - 1)] < forward[idx(k + 1)]) {
+                forward[idx(k + 1)]
+            } else {
+                forward[idx(k - 1)] + 1
+            };
+            let (x0, y0) = (x, x - k);
+            while x < n && x - k < m && old[x as usize] == new[(x - k) as usize] {
+                x += 1;
+            }
+            forward[idx(k)] = x;
+            let c = delta - k;
+            if delta % 2 != 0 && (-(d - 1)..=d - 1).contains(&c) && x + backward[idx(c)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, (x - k) as usize);
+            }
+        }
+
+        for c in (-d..=d).step_by(2) {
+            let mut x = if c == -d || (c != d && backward[idx(c - 1)] < backward[idx(c + 1)]) {
+                backward[idx(c + 1)]
+            } else {
+                backward[idx(c - 1)] + 1
+            };
+            let (x0, y0) = (x, x - c);
+            while x < n && x - c < m && old[(n - x - 1) as usize] == new[(m - (x - c) - 1) as usize]
+            {
+                x += 1;
+            }
+            backward[idx(c)] = x;
+            let k = delta - c;
+            if delta % 2 == 0 && (-d..=d).contains(&k) && forward[idx(k)] + x >= n {
+                return (
+                    (n - x) as usize,
+                    (m - (x - c)) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+
+    unreachable!("the paths of an edit script meet at half its length")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn diffs_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nc\nd\n"),
+            vec![
+                EDiffLine::Same("a".into()),
+                EDiffLine::Removed("b".into()),
+                EDiffLine::Same("c".into()),
+                EDiffLine::Added("d".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn diffs_long_files() {
+        let old = (0..20000)
+            .map(|i| format!("setgs fA{} {}\n", i, i))
+            .collect::<String>();
+        let new = old
+            .replace("setgs fA10 10\n", "")
+            .replace("setgs fA15000 15000\n", "setgs fA15000 1\nsetgs fB 2\n");
+        let diff = diff_lines(&old, &new);
+
+        let changes = diff
+            .iter()
+            .filter(|l| !matches!(l, EDiffLine::Same(_)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                &EDiffLine::Removed("setgs fA10 10".into()),
+                &EDiffLine::Removed("setgs fA15000 15000".into()),
+                &EDiffLine::Added("setgs fA15000 1".into()),
+                &EDiffLine::Added("setgs fB 2".into()),
+            ]
+        );
+        assert_eq!(diff.len(), 20002);
+    }
+
+    #[test]
+    fn keeps_newest_backups() {
+        let root = TempDir::new("backup");
+        let settings = BackupSettings {
+            dir: root.join("backups"),
+            retention: 2,
+        };
+        let target = root.join("StarfieldCustom.ini");
+
+        for i in 0..3 {
+            fs::write(&target, format!("v{}", i)).unwrap();
+            backup_file(&target, &settings).unwrap();
+        }
+        let backups = get_backups(&settings);
+        let contents = backups
+            .iter()
+            .map(|b| fs::read_to_string(&b.path).unwrap())
+            .collect::<Vec<_>>();
+
+        fs::write(&target, "changed").unwrap();
+        restore_backup(&backups[1], &settings).unwrap();
+        let restored = fs::read_to_string(&target).unwrap();
+
+        assert_eq!(contents, vec!["v2", "v1"]);
+        assert_eq!(restored, "v1");
+        assert_eq!(backups[0].target, target);
+    }
+}
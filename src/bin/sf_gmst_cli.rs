@@ -0,0 +1,627 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+// Headless command line interface to edit GMST mods and the mod load order.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use sf_gmst_editor::{
+    add_command_to_ini,
+    backup::BackupSettings,
+    database::{get_disagreements, load_gmst_dbs, merge_gmst_dbs},
+    detect_game_dir,
+    discovery::find_ini_path,
+    get_command_line, get_default_ini_path, get_diagnostics, get_load_order, get_mod_file_path,
+    get_start_commands, parse_file, parse_gmst, parse_start_command, refresh_mods, save_merged_mod,
+    save_to_file, update_mod_file, Diagnostic, EDiagnosticLevel, EGmstValue, EModType,
+    EStartCommand, GmstName, GmstNameIndex, ModViewModel,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+const USAGE: &str = "\
+Usage: sf_gmst_cli [OPTIONS] <COMMAND> [ARGS]
+
+Commands:
+  list [FILTER]              List all GMSTs and their default values
+  disagreements [FILTER]     List GMSTs whose defaults differ between the GMST databases
+  mods                       List all bat and CCR mods in load order
+  get <NAME>                 Print the default value of a GMST, or its value in --mod
+  set <NAME> <VALUE>         Set a GMST in --mod, creating the mod if needed
+  diff <MOD>                 Print all GMSTs a mod changes from their defaults
+  check <MOD>                Print warnings for broken lines of a mod
+  export <MOD>               Convert a bat mod to a CCR mod or back, written to --out
+  merge <MOD>...             Merge mods into --out in load order, the last mod wins
+  enable <MOD>               Add a bat mod to the end of the load order, keeping other commands
+  disable <MOD>              Remove a bat mod from the load order
+  reorder <MOD>...           Move the given bat mods to the front of the load order
+  write-ini [ENTRY]...       Write exactly the given bat mods and console commands to the ini
+
+Options:
+  --game-dir <DIR>  Starfield base directory (default: current directory or Steam/Xbox install)
+  --ini <FILE>      StarfieldCustom.ini path (default: MO2 profile, Proton prefix or Documents)
+  --mod <NAME>      Mod file name to read or write (default: my_gmsts)
+  --out <NAME>      Output mod file name for export and merge
+  --ccr             Use CCR mods (Data/SFSE/Plugins/ConsoleCommandRunner), like a .toml name
+  --bat             Use bat mods (default), like a .txt name
+  --backup-dir <DIR>  Folder for backups of overwritten files (default: app data folder)
+  --no-backup       Don't back up files before overwriting them
+  -h, --help        Print this help
+";
+
+#[cfg(not(target_arch = "wasm32"))]
+static BAT_NAME: &str = "my_gmsts";
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct Args {
+    game_dir: Option<PathBuf>,
+    ini: Option<PathBuf>,
+    mod_name: Option<String>,
+    out: Option<String>,
+    use_ccr: bool,
+    backup_dir: Option<PathBuf>,
+    no_backup: bool,
+    positional: Vec<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("Missing value for {}", flag));
+        match arg.as_str() {
+            "--game-dir" => parsed.game_dir = Some(PathBuf::from(value(&arg)?)),
+            "--ini" => parsed.ini = Some(PathBuf::from(value(&arg)?)),
+            "--mod" => parsed.mod_name = Some(value(&arg)?),
+            "--out" => parsed.out = Some(value(&arg)?),
+            "--ccr" => parsed.use_ccr = true,
+            "--bat" => parsed.use_ccr = false,
+            "--backup-dir" => parsed.backup_dir = Some(PathBuf::from(value(&arg)?)),
+            "--no-backup" => parsed.no_backup = true,
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => parsed.positional.push(arg),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Strips the file extension from a mod name, if any
+#[cfg(not(target_arch = "wasm32"))]
+fn mod_stem(name: &str) -> &str {
+    name.strip_suffix(".txt")
+        .or_else(|| name.strip_suffix(".toml"))
+        .unwrap_or(name)
+}
+
+/// Checks if a mod name is a CCR mod, by its extension or else by --ccr
+#[cfg(not(target_arch = "wasm32"))]
+fn is_ccr(args: &Args, name: &str) -> bool {
+    if name.ends_with(".toml") {
+        true
+    } else if name.ends_with(".txt") {
+        false
+    } else {
+        args.use_ccr
+    }
+}
+
+/// Prints a warning for each invalid line of a mod that was skipped
+#[cfg(not(target_arch = "wasm32"))]
+fn warn_skipped(path: &Path, skipped: &[Diagnostic]) {
+    for d in skipped {
+        match d.line {
+            Some(line) => eprintln!("warning: {}:{}: {}", path.display(), line, d.message),
+            None => eprintln!("warning: {}: {}", path.display(), d.message),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn sorted<K: Display>(map: &HashMap<K, EGmstValue>) -> Vec<(&K, &EGmstValue)> {
+    let mut list = map.iter().collect::<Vec<_>>();
+    list.sort_by_cached_key(|(name, _)| name.to_string());
+    list
+}
+
+/// Lists the mods in load order, CCR mods run first and disabled bat mods are listed last
+#[cfg(not(target_arch = "wasm32"))]
+fn get_mod_lines(bat_mods: &[ModViewModel], ccr_mods: &[ModViewModel]) -> Vec<String> {
+    let (enabled, disabled): (Vec<_>, Vec<_>) = bat_mods.iter().partition(|m| m.enabled);
+    let mut lines = ccr_mods
+        .iter()
+        .map(|m| format!("[ccr] {}", m.name))
+        .collect::<Vec<_>>();
+    for mod_vm in enabled.into_iter().chain(disabled) {
+        let state = if mod_vm.enabled { "x" } else { " " };
+        match mod_vm.mod_type {
+            EModType::Command => lines.push(format!("[{}] {} (command)", state, mod_vm.name)),
+            _ => lines.push(format!("[{}] {}", state, mod_vm.name)),
+        }
+    }
+    lines
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn positional(args: &Args, idx: usize, what: &str) -> Result<String, String> {
+    args.positional
+        .get(idx)
+        .cloned()
+        .ok_or(format!("Missing argument <{}>", what))
+}
+
+/// Gets the bat mods given as arguments of a load order command
+#[cfg(not(target_arch = "wasm32"))]
+fn bat_names(args: &Args) -> Vec<EStartCommand> {
+    args.positional[1..]
+        .iter()
+        .map(|n| EStartCommand::Bat(mod_stem(n).to_owned()))
+        .collect()
+}
+
+/// Compares load order entries ignoring case, like the game matches bat file names
+#[cfg(not(target_arch = "wasm32"))]
+fn is_same(a: &EStartCommand, b: &EStartCommand) -> bool {
+    a.to_string().eq_ignore_ascii_case(&b.to_string())
+}
+
+/// Returns the given entries that are not in the load order
+#[cfg(not(target_arch = "wasm32"))]
+fn find_missing(current: &[EStartCommand], names: &[EStartCommand]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|n| !current.iter().any(|c| is_same(c, n)))
+        .map(|n| n.to_string())
+        .collect()
+}
+
+/// Adds the given bat mods to the end of the load order, unless they are already in it
+#[cfg(not(target_arch = "wasm32"))]
+fn enable(current: &[EStartCommand], names: &[EStartCommand]) -> Vec<EStartCommand> {
+    let mut order = current.to_vec();
+    for name in names {
+        if !order.iter().any(|o| is_same(o, name)) {
+            order.push(name.clone());
+        }
+    }
+    order
+}
+
+/// Removes the given bat mods from the load order
+/// Fails if a mod is not in the load order
+#[cfg(not(target_arch = "wasm32"))]
+fn disable(
+    current: &[EStartCommand],
+    names: &[EStartCommand],
+) -> Result<Vec<EStartCommand>, String> {
+    let missing = find_missing(current, names);
+    if !missing.is_empty() {
+        return Err(format!("Not in the load order: {}", missing.join(", ")));
+    }
+
+    Ok(current
+        .iter()
+        .filter(|c| !names.iter().any(|n| is_same(c, n)))
+        .cloned()
+        .collect())
+}
+
+/// Moves the given bat mods to the front of the load order, in the given order
+/// Fails if a mod is not in the load order
+#[cfg(not(target_arch = "wasm32"))]
+fn reorder(
+    current: &[EStartCommand],
+    names: &[EStartCommand],
+) -> Result<Vec<EStartCommand>, String> {
+    let missing = find_missing(current, names);
+    if !missing.is_empty() {
+        return Err(format!(
+            "Not in the load order, enable them first: {}",
+            missing.join(", ")
+        ));
+    }
+
+    let mut order: Vec<EStartCommand> = vec![];
+    for entry in names.iter().chain(current) {
+        if !order.iter().any(|o| is_same(o, entry)) {
+            // keep the spelling of the ini
+            let entry = current.iter().find(|c| is_same(c, entry)).unwrap_or(entry);
+            order.push(entry.clone());
+        }
+    }
+    Ok(order)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_order(
+    ini_path: &Path,
+    order: &[EStartCommand],
+    backup: &BackupSettings,
+) -> Result<(), String> {
+    let warnings = add_command_to_ini(ini_path, order, backup).map_err(|err| err.to_string())?;
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+    println!("{}", get_command_line(order));
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run(args: Args) -> Result<(), String> {
+    let game_dir = match &args.game_dir {
+        Some(dir) => dir.to_owned(),
+        None => match detect_game_dir() {
+            Some(dir) => dir,
+            None => env::current_dir().map_err(|err| err.to_string())?,
+        },
+    };
+    let ini_path = args
+        .ini
+        .to_owned()
+        .or_else(|| find_ini_path(Some(&game_dir)))
+        .or_else(get_default_ini_path);
+    let mut backup = BackupSettings::default();
+    if let Some(dir) = &args.backup_dir {
+        backup.dir = dir.to_owned();
+    }
+    if args.no_backup {
+        backup.retention = 0;
+    }
+    let command = positional(&args, 0, "COMMAND")?;
+
+    let (dbs, errors) = load_gmst_dbs();
+    for err in errors {
+        eprintln!("warning: {}", err);
+    }
+    let defaults = merge_gmst_dbs(&dbs).values;
+    let names = GmstNameIndex::new(defaults.keys());
+    let mod_name = args.mod_name.as_deref().unwrap_or(BAT_NAME);
+    let mod_is_ccr = is_ccr(&args, mod_name);
+    let mod_path = get_mod_file_path(&game_dir, mod_is_ccr, mod_stem(mod_name));
+
+    match command.as_str() {
+        "list" => {
+            let filter = args.positional.get(1).map(|f| f.to_lowercase());
+            for (name, value) in sorted(&defaults) {
+                if let Some(filter) = &filter {
+                    if !name.to_lowercase().contains(filter) {
+                        continue;
+                    }
+                }
+                println!("{} {}", name, value);
+            }
+        }
+        "disagreements" => {
+            let filter = args.positional.get(1).map(|f| f.to_lowercase());
+            for disagreement in get_disagreements(&dbs) {
+                if let Some(filter) = &filter {
+                    if !disagreement.name.to_lowercase().contains(filter) {
+                        continue;
+                    }
+                }
+                println!("{}", disagreement.name);
+                // the last database wins
+                for (idx, (source, value)) in disagreement.values.iter().enumerate() {
+                    let used = if idx + 1 == disagreement.values.len() {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    println!("{} {} {}", used, value, source);
+                }
+            }
+        }
+        "mods" => {
+            let mods = refresh_mods(&game_dir, ini_path.as_deref(), false)
+                .map_err(|err| err.to_string())?;
+            let ccr_mods = refresh_mods(&game_dir, ini_path.as_deref(), true)
+                .map_err(|err| err.to_string())?;
+            for line in get_mod_lines(&mods, &ccr_mods) {
+                println!("{}", line);
+            }
+        }
+        "get" => {
+            let name = positional(&args, 1, "NAME")?;
+            let name = names.find(&name).ok_or(format!("Unknown GMST {}", name))?;
+            if args.mod_name.is_some() {
+                let (gmsts, skipped) =
+                    parse_file(&mod_path, mod_is_ccr).map_err(|err| err.to_string())?;
+                warn_skipped(&mod_path, &skipped);
+                let value = gmsts.get(&GmstName::parse(name)).ok_or(format!(
+                    "{} is not set in {}",
+                    name,
+                    mod_path.display()
+                ))?;
+                println!("{}", value);
+            } else {
+                println!("{}", defaults[name]);
+            }
+        }
+        "set" => {
+            let name = positional(&args, 1, "NAME")?;
+            let value = positional(&args, 2, "VALUE")?;
+            let name = names.find(&name).ok_or(format!("Unknown GMST {}", name))?;
+            let parsed =
+                parse_gmst(name, &value).ok_or(format!("Invalid value {} for {}", value, name))?;
+
+            let gmsts = HashMap::from([(name.to_owned(), parsed.clone())]);
+            update_mod_file(&gmsts, &mod_path, mod_is_ccr, &backup)
+                .map_err(|err| err.to_string())?;
+            println!("{} {} -> {}", name, parsed, mod_path.display());
+        }
+        "diff" => {
+            let name = positional(&args, 1, "MOD")?;
+            let is_ccr = is_ccr(&args, &name);
+            let path = get_mod_file_path(&game_dir, is_ccr, mod_stem(&name));
+            if !path.exists() {
+                return Err(format!("Mod {} not found", path.display()));
+            }
+            let (gmsts, skipped) = parse_file(&path, is_ccr).map_err(|err| err.to_string())?;
+            warn_skipped(&path, &skipped);
+            for (name, value) in sorted(&gmsts) {
+                match names.get(name).and_then(|n| defaults.get(n)) {
+                    Some(default_value) if default_value == value => {}
+                    Some(default_value) => println!("{} {} -> {}", name, default_value, value),
+                    None => println!("{} ? -> {}", name, value),
+                }
+            }
+        }
+        "check" => {
+            let name = positional(&args, 1, "MOD")?;
+            let is_ccr = is_ccr(&args, &name);
+            let path = get_mod_file_path(&game_dir, is_ccr, mod_stem(&name));
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+            for d in get_diagnostics(&text, is_ccr, &defaults) {
+                let level = match d.level {
+                    EDiagnosticLevel::Warning => "warning",
+                    EDiagnosticLevel::Info => "info",
+                };
+                match d.line {
+                    Some(line) => println!("{}:{}: {}: {}", path.display(), line, level, d.message),
+                    None => println!("{}: {}: {}", path.display(), level, d.message),
+                }
+            }
+        }
+        "export" => {
+            let name = positional(&args, 1, "MOD")?;
+            let is_ccr = is_ccr(&args, &name);
+            let path = get_mod_file_path(&game_dir, is_ccr, mod_stem(&name));
+            if !path.exists() {
+                return Err(format!("Mod {} not found", path.display()));
+            }
+            // always converted to the other format
+            let out_name = args.out.as_deref().map(mod_stem).unwrap_or(mod_stem(&name));
+            let out_path = get_mod_file_path(&game_dir, !is_ccr, out_name);
+            let (gmsts, skipped) = parse_file(&path, is_ccr).map_err(|err| err.to_string())?;
+            warn_skipped(&path, &skipped);
+            let gmsts = gmsts
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+            save_to_file(&gmsts, &out_path, !is_ccr, &backup).map_err(|err| err.to_string())?;
+            println!("{} -> {}", path.display(), out_path.display());
+        }
+        "merge" => {
+            let out_name = args.out.as_deref().ok_or("Missing option --out")?;
+            let out_is_ccr = is_ccr(&args, out_name);
+            let out_path = get_mod_file_path(&game_dir, out_is_ccr, mod_stem(out_name));
+
+            let bat_mods = refresh_mods(&game_dir, ini_path.as_deref(), false)
+                .map_err(|err| err.to_string())?;
+            let ccr_mods = refresh_mods(&game_dir, ini_path.as_deref(), true)
+                .map_err(|err| err.to_string())?;
+            let load_order = get_load_order(&bat_mods, &ccr_mods);
+            let mut selection = vec![];
+            for name in &args.positional[1..] {
+                let found = load_order
+                    .iter()
+                    .find(|p| {
+                        p.name.eq_ignore_ascii_case(name)
+                            || p.name.eq_ignore_ascii_case(&format!("{}.txt", name))
+                    })
+                    .ok_or(format!("Mod {} not found", name))?;
+                if found.path == out_path {
+                    return Err(format!("Refusing to overwrite {}", out_path.display()));
+                }
+                selection.push(&found.path);
+            }
+            // later mods in the load order overwrite earlier ones, like in game
+            let selected = load_order
+                .into_iter()
+                .filter(|p| selection.contains(&&p.path))
+                .collect::<Vec<_>>();
+            if selected.len() < 2 {
+                return Err("Merging needs at least two mods".into());
+            }
+
+            save_merged_mod(&selected, &out_path, out_is_ccr, &backup)
+                .map_err(|err| err.to_string())?;
+            println!("-> {}", out_path.display());
+        }
+        "write-ini" => {
+            let ini_path = ini_path.ok_or("StarfieldCustom.ini not found, use --ini")?;
+            let mut entries = vec![];
+            for arg in &args.positional[1..] {
+                // bat mods by name, everything else as console commands
+                if get_mod_file_path(&game_dir, false, mod_stem(arg)).exists() {
+                    entries.push(EStartCommand::Bat(mod_stem(arg).to_owned()));
+                } else {
+                    entries.extend(parse_start_command(arg));
+                }
+            }
+            write_order(&ini_path, &entries, &backup)?;
+        }
+        "enable" | "disable" | "reorder" => {
+            let ini_path = ini_path.ok_or("StarfieldCustom.ini not found, use --ini")?;
+            let names = bat_names(&args);
+            for name in &args.positional[1..] {
+                if !get_mod_file_path(&game_dir, false, mod_stem(name)).exists() {
+                    return Err(format!("Bat mod {}.txt not found", mod_stem(name)));
+                }
+            }
+            if names.is_empty() {
+                return Err("Missing argument <MOD>".into());
+            }
+
+            // other commands in the ini are kept
+            let current = get_start_commands(&ini_path)
+                .map_err(|err| err.to_string())?
+                .unwrap_or_default();
+            let order = match command.as_str() {
+                "enable" => enable(&current, &names),
+                "disable" => disable(&current, &names)?,
+                _ => reorder(&current, &names)?,
+            };
+            write_order(&ini_path, &order, &backup)?;
+        }
+        _ => return Err(format!("Unknown command {}\n\n{}", command, USAGE)),
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() || args.iter().any(|a| a == "-h" || a == "--help") {
+        print!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match parse_args(args.into_iter()).and_then(run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Result<Args, String> {
+        parse_args(args.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn parses_args() {
+        let parsed = args("merge a b.txt --out c --ccr --ini x.ini --no-backup").unwrap();
+        assert_eq!(parsed.positional, vec!["merge", "a", "b.txt"]);
+        assert_eq!(parsed.out.as_deref(), Some("c"));
+        assert!(parsed.use_ccr);
+        assert_eq!(parsed.ini, Some(PathBuf::from("x.ini")));
+        assert!(parsed.no_backup);
+        assert_eq!(parsed.game_dir, None);
+
+        let parsed = args("--ccr set fA 1 --bat --mod m --game-dir g --backup-dir b").unwrap();
+        assert_eq!(parsed.positional, vec!["set", "fA", "1"]);
+        assert!(!parsed.use_ccr);
+        assert_eq!(parsed.mod_name.as_deref(), Some("m"));
+        assert_eq!(parsed.game_dir, Some(PathBuf::from("g")));
+        assert_eq!(parsed.backup_dir, Some(PathBuf::from("b")));
+
+        assert_eq!(
+            args("merge a --out").err(),
+            Some("Missing value for --out".to_owned())
+        );
+        assert_eq!(
+            args("list --verbose").err(),
+            Some("Unknown option --verbose".to_owned())
+        );
+    }
+
+    #[test]
+    fn detects_ccr_mods() {
+        let bat_args = args("diff").unwrap();
+        let ccr_args = args("diff --ccr").unwrap();
+        assert!(!is_ccr(&bat_args, "a"));
+        assert!(is_ccr(&bat_args, "a.toml"));
+        assert!(is_ccr(&ccr_args, "a"));
+        assert!(!is_ccr(&ccr_args, "a.txt"));
+    }
+
+    #[test]
+    fn lists_mods_in_load_order() {
+        let get_mod = |name: &str, mod_type: EModType, enabled: bool| ModViewModel {
+            mod_type,
+            name: name.to_owned(),
+            enabled,
+            ..ModViewModel::from_command(name)
+        };
+        let bat_mods = vec![
+            get_mod("off.txt", EModType::BatMod, false),
+            get_mod("b.txt", EModType::BatMod, true),
+            get_mod("tgm", EModType::Command, true),
+            get_mod("a.txt", EModType::BatMod, true),
+        ];
+        let ccr_mods = vec![get_mod("Ship.toml", EModType::CcrMod, false)];
+
+        assert_eq!(
+            get_mod_lines(&bat_mods, &ccr_mods),
+            vec![
+                "[ccr] Ship.toml",
+                "[x] b.txt",
+                "[x] tgm (command)",
+                "[x] a.txt",
+                "[ ] off.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn reorders_bat_mods() {
+        let bat = |name: &str| EStartCommand::Bat(name.to_owned());
+        let current = vec![
+            bat("a"),
+            EStartCommand::Other("tgm".to_owned()),
+            bat("B"),
+            bat("c"),
+        ];
+
+        assert_eq!(
+            reorder(&current, &[bat("c"), bat("b"), bat("C")]),
+            Ok(vec![
+                bat("c"),
+                bat("B"),
+                bat("a"),
+                EStartCommand::Other("tgm".to_owned()),
+            ])
+        );
+        assert_eq!(
+            reorder(&current, &[bat("c"), bat("d"), bat("e")]),
+            Err("Not in the load order, enable them first: bat d, bat e".to_owned())
+        );
+    }
+
+    #[test]
+    fn enables_and_disables_bat_mods_ignoring_case() {
+        let bat = |name: &str| EStartCommand::Bat(name.to_owned());
+        let tgm = EStartCommand::Other("tgm".to_owned());
+        let current = vec![bat("my_mod"), tgm.clone()];
+
+        let names = bat_names(&args("enable MY_MOD other").unwrap());
+        assert_eq!(
+            enable(&current, &names),
+            vec![bat("my_mod"), tgm.clone(), bat("other")]
+        );
+
+        let names = bat_names(&args("disable MY_MOD.txt").unwrap());
+        assert_eq!(disable(&current, &names), Ok(vec![tgm.clone()]));
+        assert_eq!(
+            disable(&current, &[bat("other")]),
+            Err("Not in the load order: bat other".to_owned())
+        );
+    }
+}
@@ -0,0 +1,477 @@
+//! Tokenizer and syntax tree for Starfield console command lines, as used in bat files
+//!
+//! Arguments are separated by spaces or tabs, may be quoted to contain spaces,
+//! and everything after a `;` outside of quotes is a comment.
+//! Every line keeps its original text, so unknown commands round-trip unchanged
+//! and edits only touch the arguments they change.
+
+use std::{fmt::Display, ops::Range};
+
+const BOM: char = '\u{feff}';
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    /// Text without quotes
+    pub text: String,
+    pub quoted: bool,
+    /// Byte range in the line, including quotes
+    pub span: Range<usize>,
+}
+
+/// Splits a line into tokens and an optional comment after `;`
+pub fn tokenize(line: &str) -> (Vec<Token>, Option<String>) {
+    let mut tokens = vec![];
+    let mut chars = line.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            ';' => return (tokens, Some(line[start + 1..].to_owned())),
+            '"' => {
+                chars.next();
+                let mut end = line.len();
+                let mut text_end = line.len();
+                for (idx, c) in chars.by_ref() {
+                    if c == '"' {
+                        text_end = idx;
+                        end = idx + 1;
+                        break;
+                    }
+                }
+                tokens.push(Token {
+                    text: line[start + 1..text_end].to_owned(),
+                    quoted: true,
+                    span: start..end,
+                });
+            }
+            _ => {
+                let mut end = line.len();
+                while let Some(&(idx, c)) = chars.peek() {
+                    if matches!(c, ' ' | '\t' | ';' | '"') {
+                        end = idx;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(Token {
+                    text: line[start..end].to_owned(),
+                    quoted: false,
+                    span: start..end,
+                });
+            }
+        }
+    }
+
+    (tokens, None)
+}
+
+/// Quotes an argument if the console would split it
+pub fn quote_arg(arg: &str) -> String {
+    if needs_quotes(arg) {
+        format!("\"{}\"", arg)
+    } else {
+        arg.to_owned()
+    }
+}
+
+fn needs_quotes(arg: &str) -> bool {
+    arg.is_empty() || arg.contains([' ', '\t', ';', ':'])
+}
+
+/// String GMSTs start with s, their values are always quoted
+fn is_string_gmst(name: &str) -> bool {
+    name.starts_with(['s', 'S'])
+}
+
+/// Known console commands
+#[derive(Debug, Clone, PartialEq)]
+pub enum ECommand {
+    /// setgs <name> <value>
+    SetGs { name: String, value: String },
+    /// setini <name:section> <value>
+    SetIni { name: String, value: String },
+    /// bat <file>
+    Bat { file: String },
+    /// <target>.additem <item> [count]
+    AddItem { item: String, count: Option<String> },
+    /// <target>.removeitem <item> [count]
+    RemoveItem { item: String, count: Option<String> },
+    /// A known command with the wrong arguments
+    Invalid { usage: &'static str },
+    /// Any other command, kept as is
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleCommand {
+    /// Reference the command runs on, e.g. player in player.additem
+    pub target: Option<String>,
+    /// Command name as written
+    pub name: String,
+    pub args: Vec<Token>,
+    pub kind: ECommand,
+}
+
+impl ConsoleCommand {
+    fn parse(tokens: Vec<Token>) -> Option<Self> {
+        let mut tokens = tokens.into_iter();
+        let first = tokens.next()?;
+        let args = tokens.collect::<Vec<_>>();
+
+        let (target, name) = match first.text.split_once('.') {
+            Some((target, name)) if !first.quoted && !target.is_empty() && !name.is_empty() => {
+                (Some(target.to_owned()), name.to_owned())
+            }
+            _ => (None, first.text),
+        };
+
+        let arg = |idx: usize| args.get(idx).map(|t| t.text.to_owned());
+        let kind = match name.to_lowercase().as_str() {
+            "setgs" | "setgamesetting" => match args.len() {
+                2 => ECommand::SetGs {
+                    name: args[0].text.to_owned(),
+                    value: args[1].text.to_owned(),
+                },
+                _ => ECommand::Invalid {
+                    usage: "setgs <name> <value>",
+                },
+            },
+            "setini" | "setinisetting" => match args.len() {
+                2 => ECommand::SetIni {
+                    name: args[0].text.to_owned(),
+                    value: args[1].text.to_owned(),
+                },
+                _ => ECommand::Invalid {
+                    usage: "setini <name:section> <value>",
+                },
+            },
+            "bat" | "runbatchscript" => match args.len() {
+                1 => ECommand::Bat {
+                    file: args[0].text.to_owned(),
+                },
+                _ => ECommand::Invalid {
+                    usage: "bat <file>",
+                },
+            },
+            "additem" => match (args.len(), arg(0)) {
+                (1 | 2, Some(item)) => ECommand::AddItem {
+                    item,
+                    count: arg(1),
+                },
+                _ => ECommand::Invalid {
+                    usage: "<target>.additem <item> [count]",
+                },
+            },
+            "removeitem" => match (args.len(), arg(0)) {
+                (1 | 2, Some(item)) => ECommand::RemoveItem {
+                    item,
+                    count: arg(1),
+                },
+                _ => ECommand::Invalid {
+                    usage: "<target>.removeitem <item> [count]",
+                },
+            },
+            _ => ECommand::Other,
+        };
+
+        Some(ConsoleCommand {
+            target,
+            name,
+            args,
+            kind,
+        })
+    }
+
+    /// Creates a setgs command
+    pub fn setgs(name: &str, value: &str) -> Self {
+        ConsoleCommand {
+            target: None,
+            name: "setgs".to_owned(),
+            args: vec![
+                Token {
+                    text: name.to_owned(),
+                    quoted: needs_quotes(name),
+                    span: 0..0,
+                },
+                Token {
+                    text: value.to_owned(),
+                    quoted: is_string_gmst(name) || needs_quotes(value),
+                    span: 0..0,
+                },
+            ],
+            kind: ECommand::SetGs {
+                name: name.to_owned(),
+                value: value.to_owned(),
+            },
+        }
+    }
+}
+
+/// Formats the command in canonical form, single spaces and quoted args where needed
+impl Display for ConsoleCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(target) = &self.target {
+            write!(f, "{}.", target)?;
+        }
+        write!(f, "{}", self.name)?;
+        for arg in &self.args {
+            match arg.quoted {
+                true => write!(f, " \"{}\"", arg.text)?,
+                false => write!(f, " {}", arg.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsoleLine {
+    /// Original text without the line ending
+    pub text: String,
+    /// None for blank and comment lines
+    pub command: Option<ConsoleCommand>,
+    /// Text after `;`
+    pub comment: Option<String>,
+    /// "\r\n", "\n" or "" for the last line
+    pub ending: String,
+}
+
+impl ConsoleLine {
+    pub fn parse(text: &str) -> Self {
+        let (tokens, comment) = tokenize(text);
+        ConsoleLine {
+            text: text.to_owned(),
+            command: ConsoleCommand::parse(tokens),
+            comment,
+            ending: String::new(),
+        }
+    }
+
+    /// Replaces an argument of the command, the rest of the line is kept as is
+    /// Quoted arguments stay quoted
+    pub fn set_arg(&mut self, idx: usize, value: &str) -> bool {
+        let Some(arg) = self.command.as_ref().and_then(|c| c.args.get(idx)) else {
+            return false;
+        };
+        let span = arg.span.clone();
+        let quoted = match arg.quoted {
+            true => format!("\"{}\"", value),
+            false => quote_arg(value),
+        };
+
+        let mut text = self.text.to_owned();
+        text.replace_range(span, &quoted);
+        let ending = std::mem::take(&mut self.ending);
+        *self = ConsoleLine::parse(&text);
+        self.ending = ending;
+        true
+    }
+}
+
+/// Writes the original text
+impl Display for ConsoleLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.text, self.ending)
+    }
+}
+
+/// A bat file, one command per line
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConsoleFile {
+    /// The file starts with a UTF-8 byte order mark
+    pub bom: bool,
+    pub lines: Vec<ConsoleLine>,
+}
+
+impl ConsoleFile {
+    pub fn parse(text: &str) -> Self {
+        let (bom, text) = match text.strip_prefix(BOM) {
+            Some(stripped) => (true, stripped),
+            None => (false, text),
+        };
+
+        let mut lines = vec![];
+        for raw in text.split_inclusive('\n') {
+            let (line, ending) = if let Some(line) = raw.strip_suffix("\r\n") {
+                (line, "\r\n")
+            } else if let Some(line) = raw.strip_suffix('\n') {
+                (line, "\n")
+            } else {
+                (raw, "")
+            };
+            let mut line = ConsoleLine::parse(line);
+            line.ending = ending.to_owned();
+            lines.push(line);
+        }
+
+        ConsoleFile { bom, lines }
+    }
+
+    /// The line ending used by this file, LF for new files
+    pub fn line_ending(&self) -> &str {
+        self.lines
+            .iter()
+            .map(|l| l.ending.as_str())
+            .find(|e| !e.is_empty())
+            .unwrap_or("\n")
+    }
+
+    /// Finds the last setgs line of a GMST, ignoring case like the game does
+    fn find_gmst(&self, name: &str) -> Option<usize> {
+        self.lines.iter().rposition(|l| {
+            matches!(
+                l.command.as_ref().map(|c| &c.kind),
+                Some(ECommand::SetGs { name: n, .. }) if n.eq_ignore_ascii_case(name)
+            )
+        })
+    }
+
+    /// Gets the value of the last setgs line of a GMST
+    pub fn get_gmst(&self, name: &str) -> Option<&str> {
+        match &self.lines[self.find_gmst(name)?].command.as_ref()?.kind {
+            ECommand::SetGs { value, .. } => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Sets a GMST, the value of an existing setgs line is replaced in place,
+    /// new GMSTs are appended to the end of the file
+    pub fn set_gmst(&mut self, name: &str, value: &str) {
+        if let Some(idx) = self.find_gmst(name) {
+            self.lines[idx].set_arg(1, value);
+            return;
+        }
+
+        let ending = self.line_ending().to_owned();
+        if let Some(last) = self.lines.last_mut() {
+            if last.ending.is_empty() {
+                last.ending = ending.to_owned();
+            }
+        }
+        let mut line = ConsoleLine::parse(&ConsoleCommand::setgs(name, value).to_string());
+        line.ending = ending;
+        self.lines.push(line);
+    }
+}
+
+impl Display for ConsoleFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.bom {
+            write!(f, "{}", BOM)?;
+        }
+        for line in &self.lines {
+            write!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_quotes_tabs_and_comments() {
+        let (tokens, comment) = tokenize("setgs\t \"fFoo Bar:General\"   1.5 ; my \"comment\"");
+        assert_eq!(
+            tokens.iter().map(|t| t.text.as_str()).collect::<Vec<_>>(),
+            vec!["setgs", "fFoo Bar:General", "1.5"]
+        );
+        assert!(tokens[1].quoted);
+        assert_eq!(tokens[1].span, 7..25);
+        assert_eq!(comment.as_deref(), Some(" my \"comment\""));
+
+        let (tokens, comment) = tokenize("bat \"a;b\";c");
+        assert_eq!(tokens[1].text, "a;b");
+        assert_eq!(comment.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn parses_commands() {
+        let kind = |line: &str| ConsoleLine::parse(line).command.map(|c| c.kind);
+        assert_eq!(
+            kind("SetGS  \"fJumpHeightMin\" 2"),
+            Some(ECommand::SetGs {
+                name: "fJumpHeightMin".into(),
+                value: "2".into()
+            })
+        );
+        assert_eq!(
+            kind("setini \"bFoo:General\" 1"),
+            Some(ECommand::SetIni {
+                name: "bFoo:General".into(),
+                value: "1".into()
+            })
+        );
+        assert_eq!(
+            kind("bat my_gmsts"),
+            Some(ECommand::Bat {
+                file: "my_gmsts".into()
+            })
+        );
+        assert_eq!(
+            kind("setgs fFoo"),
+            Some(ECommand::Invalid {
+                usage: "setgs <name> <value>"
+            })
+        );
+        assert_eq!(kind("tgm"), Some(ECommand::Other));
+        assert_eq!(kind("  ; only a comment"), None);
+        assert_eq!(kind(""), None);
+
+        let command = ConsoleLine::parse("player.additem 0000000F 1000")
+            .command
+            .unwrap();
+        assert_eq!(command.target.as_deref(), Some("player"));
+        assert_eq!(
+            command.kind,
+            ECommand::AddItem {
+                item: "0000000F".into(),
+                count: Some("1000".into())
+            }
+        );
+    }
+
+    #[test]
+    fn round_trips_lines() {
+        for text in [
+            "  player.additem f 1 ;money",
+            "\tunknown \"a b\"  c",
+            "; comment",
+            "",
+        ] {
+            assert_eq!(ConsoleLine::parse(text).to_string(), text);
+        }
+        assert_eq!(
+            ConsoleCommand::setgs("fFoo:Bar", "1").to_string(),
+            "setgs \"fFoo:Bar\" 1"
+        );
+        assert_eq!(
+            ConsoleCommand::setgs("sName", "Bob").to_string(),
+            "setgs sName \"Bob\""
+        );
+    }
+
+    #[test]
+    fn sets_gmsts_in_place() {
+        let text = "; my mod\r\nsetgs\tfA  1 ; keep\r\ntgm\r\nsetgs fB 2";
+        let mut file = ConsoleFile::parse(text);
+        assert_eq!(file.to_string(), text);
+        assert_eq!(file.get_gmst("fa"), Some("1"));
+
+        file.set_gmst("fA", "1.5");
+        file.set_gmst("fC", "3");
+        assert_eq!(
+            file.to_string(),
+            "; my mod\r\nsetgs\tfA  1.5 ; keep\r\ntgm\r\nsetgs fB 2\r\nsetgs fC 3\r\n"
+        );
+
+        let mut file = ConsoleFile::parse("setgs sA \"x\"\n");
+        file.set_gmst("sA", "y");
+        file.set_gmst("sB", "a b");
+        assert_eq!(file.get_gmst("sb"), Some("a b"));
+        assert_eq!(file.to_string(), "setgs sA \"y\"\nsetgs sB \"a b\"\n");
+    }
+}
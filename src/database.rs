@@ -0,0 +1,415 @@
+//! GMST default databases
+//!
+//! The embedded databases are the fallback. User databases in the config folder
+//! (`databases/*.csv`, `*.toml` or `*.json`) are applied on top of them in file name order,
+//! so settings added or changed by a game patch don't need a new release.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{self, BufRead},
+    path::PathBuf,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::{Error, Result};
+use crate::{parse_gmst, EGmstValue};
+#[cfg(not(target_arch = "wasm32"))]
+use directories::ProjectDirs;
+
+/// Embedded databases, lowest priority first
+const EMBEDDED_DBS: [(&str, &[u8]); 2] = [
+    ("ghidra_gmsts.csv", include_bytes!("ghidra_gmsts.csv")),
+    (
+        "Starfield_Game_Settings.csv",
+        include_bytes!("Starfield_Game_Settings.csv"),
+    ),
+];
+
+/// Where a GMST default comes from
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum EGmstSource {
+    /// A database built into the app
+    Embedded(String),
+    /// A user database file
+    User(PathBuf),
+}
+
+impl Display for EGmstSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EGmstSource::Embedded(name) => write!(f, "{} (embedded)", name),
+            EGmstSource::User(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// The GMSTs of one database
+#[derive(Debug, Clone, PartialEq)]
+pub struct GmstDb {
+    pub source: EGmstSource,
+    pub gmsts: HashMap<String, EGmstValue>,
+}
+
+/// The merged defaults of all databases
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GmstDefaults {
+    pub values: HashMap<String, EGmstValue>,
+    /// All databases that agree on each value, lowest priority first
+    pub sources: HashMap<String, Vec<EGmstSource>>,
+}
+
+/// A GMST whose default differs between databases
+#[derive(Debug, Clone, PartialEq)]
+pub struct GmstDisagreement {
+    pub name: String,
+    /// The value of each database, lowest priority first
+    pub values: Vec<(EGmstSource, EGmstValue)>,
+}
+
+impl GmstDisagreement {
+    /// The value that is used, from the database with the highest priority
+    pub fn get_used_value(&self) -> Option<&EGmstValue> {
+        self.values.last().map(|(_, value)| value)
+    }
+}
+
+/// Parses a CSV database with name,value lines, the first value of a GMST wins
+pub fn parse_csv(bytes: &[u8]) -> HashMap<String, EGmstValue> {
+    parse_csv_lines(bytes).0
+}
+
+/// Parses CSV name,value lines, returns the GMSTs and the 1-based lines that are invalid
+fn parse_csv_lines(bytes: &[u8]) -> (HashMap<String, EGmstValue>, Vec<(usize, String)>) {
+    let mut map = HashMap::default();
+    let mut invalid = vec![];
+    let reader = io::BufReader::new(bytes);
+    for (idx, line) in reader.lines().enumerate() {
+        let Ok(str) = line else {
+            continue;
+        };
+        let Some((name, value)) = str.split_once(',') else {
+            if !str.trim().is_empty() {
+                invalid.push((idx + 1, "expected name,value".to_owned()));
+            }
+            continue;
+        };
+        let name = name.trim();
+        // string values may be quoted to keep commas and spaces
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        match parse_gmst(name, value) {
+            Some(parsed) => {
+                map.entry(name.to_owned()).or_insert(parsed);
+            }
+            None => invalid.push((idx + 1, format!("invalid value for {}", name))),
+        }
+    }
+    (map, invalid)
+}
+
+/// Gets the databases built into the app, lowest priority first
+pub fn get_embedded_dbs() -> Vec<GmstDb> {
+    EMBEDDED_DBS
+        .iter()
+        .map(|(name, bytes)| GmstDb {
+            source: EGmstSource::Embedded(name.to_string()),
+            gmsts: parse_csv(bytes),
+        })
+        .collect()
+}
+
+/// Merges databases, later databases override earlier ones
+pub fn merge_gmst_dbs(dbs: &[GmstDb]) -> GmstDefaults {
+    let mut defaults = GmstDefaults::default();
+    for db in dbs {
+        for (name, value) in &db.gmsts {
+            let sources = defaults.sources.entry(name.to_owned()).or_default();
+            if defaults.values.get(name) != Some(value) {
+                sources.clear();
+                defaults.values.insert(name.to_owned(), value.clone());
+            }
+            sources.push(db.source.clone());
+        }
+    }
+    defaults
+}
+
+/// Gets all GMSTs whose defaults differ between databases, sorted by name
+pub fn get_disagreements(dbs: &[GmstDb]) -> Vec<GmstDisagreement> {
+    let mut map: HashMap<&String, Vec<(EGmstSource, EGmstValue)>> = HashMap::default();
+    for db in dbs {
+        for (name, value) in &db.gmsts {
+            map.entry(name)
+                .or_default()
+                .push((db.source.clone(), value.clone()));
+        }
+    }
+
+    let mut disagreements = map
+        .into_iter()
+        .filter(|(_, values)| values.iter().any(|(_, v)| *v != values[0].1))
+        .map(|(name, values)| GmstDisagreement {
+            name: name.to_owned(),
+            values,
+        })
+        .collect::<Vec<_>>();
+    disagreements.sort_by(|a, b| a.name.cmp(&b.name));
+    disagreements
+}
+
+/// The folder of the user databases
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_user_db_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "sf_gmst_editor").map(|p| p.config_dir().join("databases"))
+}
+
+/// Parses a user database, the format is picked by the extension
+/// CSV files have name,value lines, TOML and JSON files map names to values.
+/// Invalid values are skipped and returned as errors, the file fails only if it can't be parsed
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse_db_file(path: &Path) -> Result<(HashMap<String, EGmstValue>, Vec<Error>)> {
+    let text = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let mut values: Vec<(String, Option<String>)> = match extension.as_str() {
+        "csv" => {
+            let (map, invalid) = parse_csv_lines(text.as_bytes());
+            let errors = invalid
+                .into_iter()
+                .map(|(line, message)| Error::Database {
+                    path: path.to_owned(),
+                    line: Some(line),
+                    message,
+                })
+                .collect();
+            return Ok((map, errors));
+        }
+        "toml" => toml::from_str::<HashMap<String, toml::Value>>(&text)
+            .map_err(|err| Error::toml(path, &text, err))?
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    toml::Value::String(s) => Some(s),
+                    toml::Value::Integer(i) => Some(i.to_string()),
+                    toml::Value::Float(f) => Some(f.to_string()),
+                    toml::Value::Boolean(b) => Some(b.to_string()),
+                    _ => None,
+                };
+                (name, value)
+            })
+            .collect(),
+        "json" => serde_json::from_str::<HashMap<String, serde_json::Value>>(&text)
+            .map_err(|err| Error::Database {
+                path: path.to_owned(),
+                line: Some(err.line()),
+                message: err.to_string(),
+            })?
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::String(s) => Some(s),
+                    serde_json::Value::Number(n) => Some(n.to_string()),
+                    serde_json::Value::Bool(b) => Some(b.to_string()),
+                    _ => None,
+                };
+                (name, value)
+            })
+            .collect(),
+        _ => {
+            return Err(Error::Database {
+                path: path.to_owned(),
+                line: None,
+                message: "expected a .csv, .toml or .json file".to_owned(),
+            })
+        }
+    };
+
+    values.sort();
+
+    let mut map = HashMap::default();
+    let mut errors = vec![];
+    for (name, value) in values {
+        match value.as_deref().and_then(|v| parse_gmst(&name, v)) {
+            Some(parsed) => {
+                map.insert(name, parsed);
+            }
+            None => errors.push(Error::Database {
+                path: path.to_owned(),
+                line: None,
+                message: format!("invalid value for {}", name),
+            }),
+        }
+    }
+    Ok((map, errors))
+}
+
+/// Loads all user databases in a folder in file name order
+/// Broken files and invalid values are skipped and returned as errors
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_user_dbs(dir: &Path) -> (Vec<GmstDb>, Vec<Error>) {
+    let mut dbs = vec![];
+    let mut errors = vec![];
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return (dbs, errors),
+        Err(err) => return (dbs, vec![Error::io(dir, err)]),
+    };
+    let mut paths = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            let extension = p.extension().map(|e| e.to_string_lossy().to_lowercase());
+            p.is_file() && matches!(extension.as_deref(), Some("csv" | "toml" | "json"))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        match parse_db_file(&path) {
+            Ok((gmsts, invalid)) => {
+                dbs.push(GmstDb {
+                    source: EGmstSource::User(path),
+                    gmsts,
+                });
+                errors.extend(invalid);
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    (dbs, errors)
+}
+
+/// Loads the embedded databases and the user databases on top of them
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_gmst_dbs() -> (Vec<GmstDb>, Vec<Error>) {
+    let mut dbs = get_embedded_dbs();
+    let mut errors = vec![];
+    if let Some(dir) = get_user_db_dir() {
+        let (user_dbs, user_errors) = get_user_dbs(&dir);
+        dbs.extend(user_dbs);
+        errors = user_errors;
+    }
+    (dbs, errors)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn user_dbs_override_embedded() {
+        let dir = TempDir::new("db");
+        std::fs::write(dir.join("a.csv"), "fJumpHeightMin,2\nfNew,1\niBad,x\n").unwrap();
+        std::fs::write(
+            dir.join("b.toml"),
+            "fJumpHeightMin = 3.5\nsName = \"a, b\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("c.json"), "{\"iNew\": 4, \"bNew\": true}").unwrap();
+        std::fs::write(dir.join("d.json"), "{\"iBroken\": 1.5, \"iFine\": 2}").unwrap();
+        std::fs::write(dir.join("e.toml"), "iBroken = \n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let (user_dbs, errors) = get_user_dbs(dir.path());
+
+        let mut dbs = get_embedded_dbs();
+        dbs.extend(user_dbs);
+        let defaults = merge_gmst_dbs(&dbs);
+
+        // invalid values are skipped in every format, broken files are skipped entirely
+        let errors = errors
+            .iter()
+            .map(|err| match err {
+                Error::Database { path, line, .. } => (path.to_owned(), *line),
+                err => (err.path().to_owned(), None),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (dir.join("a.csv"), Some(3)),
+                (dir.join("d.json"), None),
+                (dir.join("e.toml"), None),
+            ]
+        );
+        assert_eq!(dbs.len(), 6);
+        assert!(defaults.values["iFine"] == EGmstValue::Int(2));
+        assert!(!defaults.values.contains_key("iBad"));
+        assert!(!defaults.values.contains_key("iBroken"));
+        assert!(defaults.values["fJumpHeightMin"] == EGmstValue::Float(3.5));
+        assert_eq!(
+            defaults.sources["fJumpHeightMin"],
+            vec![EGmstSource::User(dir.join("b.toml"))]
+        );
+        assert!(defaults.values["sName"] == EGmstValue::String("a, b".to_owned()));
+        assert!(defaults.values["iNew"] == EGmstValue::Int(4));
+        assert!(defaults.values["bNew"] == EGmstValue::Bool(true));
+        assert_eq!(
+            defaults.sources["fAVDCarryWeightBase"],
+            vec![EGmstSource::Embedded(
+                "Starfield_Game_Settings.csv".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_disagreements() {
+        let db = |name: &str, gmsts: &[(&str, EGmstValue)]| GmstDb {
+            source: EGmstSource::Embedded(name.to_owned()),
+            gmsts: gmsts
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.clone()))
+                .collect(),
+        };
+        let dbs = [
+            db(
+                "a",
+                &[
+                    ("bA", EGmstValue::Bool(true)),
+                    ("fB", EGmstValue::Float(1.0)),
+                ],
+            ),
+            db(
+                "b",
+                &[
+                    ("bA", EGmstValue::Bool(false)),
+                    ("fB", EGmstValue::Float(1.0)),
+                ],
+            ),
+        ];
+
+        let disagreements = get_disagreements(&dbs);
+        assert_eq!(disagreements.len(), 1);
+        assert_eq!(disagreements[0].name, "bA");
+        assert_eq!(
+            disagreements[0].get_used_value(),
+            Some(&EGmstValue::Bool(false))
+        );
+
+        let defaults = merge_gmst_dbs(&dbs);
+        assert_eq!(defaults.sources["bA"], vec![dbs[1].source.clone()]);
+        assert_eq!(
+            defaults.sources["fB"],
+            vec![dbs[0].source.clone(), dbs[1].source.clone()]
+        );
+
+        let embedded = get_disagreements(&get_embedded_dbs());
+        let blinks = embedded
+            .iter()
+            .find(|d| d.name == "bAllowBlinksDuringSpeech")
+            .unwrap();
+        assert_eq!(blinks.values.len(), 2);
+    }
+}
@@ -0,0 +1,493 @@
+//! Finds the Starfield install directory in Steam libraries and Xbox/Game Pass layouts,
+//! and the StarfieldCustom.ini in the documents folder, Proton prefixes and MO2 profiles
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use directories::BaseDirs;
+
+use crate::{get_default_ini_path, is_game_dir};
+
+/// Starfield's Steam app id
+pub const STARFIELD_APP_ID: &str = "1716740";
+
+/// A value in a Valve KeyValues (vdf/acf) file
+#[derive(Debug, PartialEq, Clone)]
+pub enum VdfValue {
+    String(String),
+    Object(Vec<(String, VdfValue)>),
+}
+
+impl VdfValue {
+    /// Gets a child value by key, ignoring case
+    pub fn get(&self, key: &str) -> Option<&VdfValue> {
+        match self {
+            VdfValue::Object(children) => children
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            VdfValue::String(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            VdfValue::String(s) => Some(s.as_str()),
+            VdfValue::Object(_) => None,
+        }
+    }
+
+    pub fn children(&self) -> &[(String, VdfValue)] {
+        match self {
+            VdfValue::Object(children) => children.as_slice(),
+            VdfValue::String(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum EVdfToken {
+    String(String),
+    Open,
+    Close,
+}
+
+fn tokenize_vdf(text: &str) -> Option<Vec<EVdfToken>> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(EVdfToken::Open),
+            '}' => tokens.push(EVdfToken::Close),
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            'n' => s.push('\n'),
+                            't' => s.push('\t'),
+                            other => s.push(other),
+                        },
+                        other => s.push(other),
+                    }
+                }
+                tokens.push(EVdfToken::String(s));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                // comment until end of line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                // unquoted string
+                let mut s = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '{' || next == '}' || next == '"' {
+                        break;
+                    }
+                    s.push(next);
+                    chars.next();
+                }
+                tokens.push(EVdfToken::String(s));
+            }
+        }
+    }
+    Some(tokens)
+}
+
+fn parse_vdf_object(tokens: &mut std::vec::IntoIter<EVdfToken>, is_root: bool) -> Option<VdfValue> {
+    let mut children = vec![];
+    loop {
+        let key = match tokens.next() {
+            Some(EVdfToken::String(key)) => key,
+            Some(EVdfToken::Close) if !is_root => break,
+            None if is_root => break,
+            _ => return None,
+        };
+        let value = match tokens.next()? {
+            EVdfToken::String(value) => VdfValue::String(value),
+            EVdfToken::Open => parse_vdf_object(tokens, false)?,
+            EVdfToken::Close => return None,
+        };
+        children.push((key, value));
+    }
+    Some(VdfValue::Object(children))
+}
+
+/// Parses the text of a Valve KeyValues file, e.g. libraryfolders.vdf
+/// Returns None if the file is malformed
+pub fn parse_vdf(text: &str) -> Option<VdfValue> {
+    let mut tokens = tokenize_vdf(text)?.into_iter();
+    parse_vdf_object(&mut tokens, true)
+}
+
+/// Gets all Steam library folders listed in libraryfolders.vdf
+pub fn get_library_folders(vdf: &str) -> Vec<PathBuf> {
+    let Some(root) = parse_vdf(vdf) else {
+        return vec![];
+    };
+    let Some(libraries) = root.get("libraryfolders") else {
+        return vec![];
+    };
+
+    let mut folders = vec![];
+    for (key, value) in libraries.children() {
+        match value {
+            // new format: "0" { "path" "D:\\SteamLibrary" ... }
+            VdfValue::Object(_) => {
+                if let Some(path) = value.get("path").and_then(VdfValue::as_str) {
+                    folders.push(PathBuf::from(path));
+                }
+            }
+            // old format: "1" "D:\\SteamLibrary"
+            VdfValue::String(path) => {
+                if key.parse::<u32>().is_ok() {
+                    folders.push(PathBuf::from(path));
+                }
+            }
+        }
+    }
+    folders
+}
+
+/// Gets the install dir name from an appmanifest acf file
+pub fn get_install_dir(acf: &str) -> Option<String> {
+    parse_vdf(acf)?
+        .get("AppState")?
+        .get("installdir")?
+        .as_str()
+        .map(|s| s.to_owned())
+}
+
+/// Finds Starfield in all libraries of a Steam installation
+pub fn find_steam_install(steam_root: &Path) -> Option<PathBuf> {
+    let vdf_path = steam_root.join("steamapps").join("libraryfolders.vdf");
+    let mut libraries = vec![steam_root.to_path_buf()];
+    if let Ok(vdf) = std::fs::read_to_string(vdf_path) {
+        for library in get_library_folders(&vdf) {
+            if !libraries.contains(&library) {
+                libraries.push(library);
+            }
+        }
+    }
+
+    for library in libraries {
+        let steamapps = library.join("steamapps");
+        let manifest = steamapps.join(format!("appmanifest_{}.acf", STARFIELD_APP_ID));
+        let Ok(acf) = std::fs::read_to_string(manifest) else {
+            continue;
+        };
+        if let Some(install_dir) = get_install_dir(&acf) {
+            let game_dir = steamapps.join("common").join(install_dir);
+            if is_game_dir(&game_dir) {
+                return Some(game_dir);
+            }
+        }
+    }
+    None
+}
+
+/// Gets the default Steam install locations of this platform
+pub fn get_steam_roots() -> Vec<PathBuf> {
+    let mut roots = vec![];
+    if cfg!(windows) {
+        for var in ["ProgramFiles(x86)", "ProgramFiles"] {
+            if let Ok(dir) = std::env::var(var) {
+                roots.push(PathBuf::from(dir).join("Steam"));
+            }
+        }
+        roots.push(PathBuf::from(r"C:\Program Files (x86)\Steam"));
+    } else if let Some(base_dirs) = BaseDirs::new() {
+        let home = base_dirs.home_dir();
+        roots.push(home.join(".steam").join("steam"));
+        roots.push(home.join(".local").join("share").join("Steam"));
+        roots.push(
+            home.join(".var")
+                .join("app")
+                .join("com.valvesoftware.Steam")
+                .join(".local")
+                .join("share")
+                .join("Steam"),
+        );
+    }
+    dedup_paths(&mut roots);
+    roots
+}
+
+/// Removes duplicate paths and keeps the first one, Windows paths are compared ignoring case
+fn dedup_paths(paths: &mut Vec<PathBuf>) {
+    let mut seen = HashSet::new();
+    paths.retain(|path| {
+        let key = path.to_string_lossy();
+        match cfg!(windows) {
+            true => seen.insert(key.to_lowercase()),
+            false => seen.insert(key.into_owned()),
+        }
+    });
+}
+
+/// Gets the possible Xbox/Game Pass install locations on all drives
+pub fn get_xbox_install_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if cfg!(windows) {
+        for drive in 'C'..='Z' {
+            let root = PathBuf::from(format!("{}:\\", drive));
+            if !root.exists() {
+                continue;
+            }
+            dirs.push(root.join("XboxGames").join("Starfield").join("Content"));
+            dirs.push(
+                root.join("Program Files")
+                    .join("ModifiableWindowsApps")
+                    .join("Starfield"),
+            );
+        }
+    }
+    dirs
+}
+
+/// Searches Steam libraries and Xbox/Game Pass layouts for the Starfield base directory
+pub fn find_game_dir() -> Option<PathBuf> {
+    for root in get_steam_roots() {
+        if let Some(game_dir) = find_steam_install(&root) {
+            return Some(game_dir);
+        }
+    }
+    get_xbox_install_dirs().into_iter().find(|p| is_game_dir(p))
+}
+
+/// Gets the StarfieldCustom.ini path in the Proton prefix of a steamapps folder
+pub fn get_proton_ini_path(steamapps: &Path) -> PathBuf {
+    steamapps
+        .join("compatdata")
+        .join(STARFIELD_APP_ID)
+        .join("pfx")
+        .join("drive_c")
+        .join("users")
+        .join("steamuser")
+        .join("Documents")
+        .join("My Games")
+        .join("Starfield")
+        .join("StarfieldCustom.ini")
+}
+
+/// Gets the selected profile from the text of a ModOrganizer.ini,
+/// if the instance manages Starfield
+pub fn get_mo2_selected_profile(mo2_ini: &str) -> Option<String> {
+    let mut is_starfield = false;
+    let mut profile = None;
+    for line in mo2_ini.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "gameName" => is_starfield = value.trim().eq_ignore_ascii_case("Starfield"),
+            "selected_profile" => {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix("@ByteArray(")
+                    .and_then(|v| v.strip_suffix(')'))
+                    .unwrap_or(value);
+                profile = Some(value.to_owned());
+            }
+            _ => {}
+        }
+    }
+    profile.filter(|_| is_starfield)
+}
+
+/// Gets all MO2 instance directories: global instances and a portable instance next to this app
+pub fn get_mo2_instances() -> Vec<PathBuf> {
+    let mut instances = vec![];
+    if let Some(base_dirs) = BaseDirs::new() {
+        let global = base_dirs.data_local_dir().join("ModOrganizer");
+        if let Ok(entries) = std::fs::read_dir(global) {
+            for entry in entries.flatten() {
+                instances.push(entry.path());
+            }
+        }
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        for dir in exe.ancestors().skip(1) {
+            if dir.join("ModOrganizer.ini").exists() {
+                instances.push(dir.to_path_buf());
+                break;
+            }
+        }
+    }
+    instances
+}
+
+/// Gets the StarfieldCustom.ini of the selected profile in a MO2 instance
+/// This only exists if the profile uses profile-specific game INI files
+pub fn get_mo2_profile_ini(instance_dir: &Path) -> Option<PathBuf> {
+    let mo2_ini = std::fs::read_to_string(instance_dir.join("ModOrganizer.ini")).ok()?;
+    let profile = get_mo2_selected_profile(&mo2_ini)?;
+    let ini_path = instance_dir
+        .join("profiles")
+        .join(profile)
+        .join("StarfieldCustom.ini");
+    ini_path.exists().then_some(ini_path)
+}
+
+/// Gets all possible StarfieldCustom.ini locations, most specific first
+pub fn get_ini_candidates(game_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut candidates = vec![];
+
+    // MO2 profile-local ini
+    for instance in get_mo2_instances() {
+        if let Some(ini_path) = get_mo2_profile_ini(&instance) {
+            candidates.push(ini_path);
+        }
+    }
+
+    // Proton prefix next to the game, then in all Steam libraries
+    if !cfg!(windows) {
+        if let Some(steamapps) = game_dir.and_then(|p| p.parent()).and_then(|p| p.parent()) {
+            candidates.push(get_proton_ini_path(steamapps));
+        }
+        for root in get_steam_roots() {
+            let vdf_path = root.join("steamapps").join("libraryfolders.vdf");
+            let mut libraries = vec![root];
+            if let Ok(vdf) = std::fs::read_to_string(vdf_path) {
+                libraries.extend(get_library_folders(&vdf));
+            }
+            for library in libraries {
+                let ini_path = get_proton_ini_path(&library.join("steamapps"));
+                if !candidates.contains(&ini_path) {
+                    candidates.push(ini_path);
+                }
+            }
+        }
+    }
+
+    // documents
+    if let Some(ini_path) = get_default_ini_path() {
+        candidates.push(ini_path);
+    }
+
+    candidates
+}
+
+/// Finds the StarfieldCustom.ini the game uses
+pub fn find_ini_path(game_dir: Option<&Path>) -> Option<PathBuf> {
+    get_ini_candidates(game_dir)
+        .into_iter()
+        .find(|p| p.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const LIBRARY_FOLDERS: &str = include_str!("../tests/fixtures/libraryfolders.vdf");
+    const LIBRARY_FOLDERS_OLD: &str = include_str!("../tests/fixtures/libraryfolders_old.vdf");
+    const APP_MANIFEST: &str = include_str!("../tests/fixtures/appmanifest_1716740.acf");
+
+    #[test]
+    fn dedups_paths() {
+        let mut paths = vec![
+            PathBuf::from("/games/Steam"),
+            PathBuf::from("/other/Steam"),
+            PathBuf::from("/games/Steam"),
+            PathBuf::from("/games/steam"),
+        ];
+        dedup_paths(&mut paths);
+        let expected = match cfg!(windows) {
+            true => vec![PathBuf::from("/games/Steam"), PathBuf::from("/other/Steam")],
+            false => vec![
+                PathBuf::from("/games/Steam"),
+                PathBuf::from("/other/Steam"),
+                PathBuf::from("/games/steam"),
+            ],
+        };
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn parses_library_folders() {
+        assert_eq!(
+            get_library_folders(LIBRARY_FOLDERS),
+            vec![
+                PathBuf::from(r"C:\Program Files (x86)\Steam"),
+                PathBuf::from(r"D:\SteamLibrary"),
+                PathBuf::from("/home/deck/.local/share/Steam"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_old_library_folders() {
+        assert_eq!(
+            get_library_folders(LIBRARY_FOLDERS_OLD),
+            vec![
+                PathBuf::from(r"D:\SteamLibrary"),
+                PathBuf::from(r"E:\Games")
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_app_manifest() {
+        assert_eq!(get_install_dir(APP_MANIFEST), Some("Starfield".to_owned()));
+    }
+
+    #[test]
+    fn rejects_malformed_vdf() {
+        assert_eq!(parse_vdf("\"libraryfolders\" { \"0\" {"), None);
+        assert_eq!(parse_vdf("\"unterminated"), None);
+        assert!(get_library_folders("garbage {").is_empty());
+    }
+
+    #[test]
+    fn parses_mo2_selected_profile() {
+        let mo2_ini = "[General]\ngameName=Starfield\nselected_profile=@ByteArray(Survival)\n";
+        assert_eq!(
+            get_mo2_selected_profile(mo2_ini),
+            Some("Survival".to_owned())
+        );
+
+        let other_game =
+            "[General]\ngameName=Skyrim Special Edition\nselected_profile=@ByteArray(Default)\n";
+        assert_eq!(get_mo2_selected_profile(other_game), None);
+    }
+
+    #[test]
+    fn proton_ini_is_in_compatdata() {
+        assert_eq!(
+            get_proton_ini_path(Path::new("/home/deck/.local/share/Steam/steamapps")),
+            PathBuf::from("/home/deck/.local/share/Steam/steamapps/compatdata/1716740/pfx/drive_c/users/steamuser/Documents/My Games/Starfield/StarfieldCustom.ini")
+        );
+    }
+
+    #[test]
+    fn finds_game_in_secondary_library() {
+        let root = TempDir::new("discovery");
+        let steam = root.join("Steam");
+        let library = root.join("Library");
+        let game_dir = library.join("steamapps").join("common").join("Starfield");
+        std::fs::create_dir_all(steam.join("steamapps")).unwrap();
+        std::fs::create_dir_all(&game_dir).unwrap();
+        std::fs::write(game_dir.join("Starfield.exe"), "").unwrap();
+        std::fs::write(
+            library.join("steamapps").join("appmanifest_1716740.acf"),
+            APP_MANIFEST,
+        )
+        .unwrap();
+        let vdf = format!(
+            "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+            library.display().to_string().replace('\\', "\\\\")
+        );
+        std::fs::write(steam.join("steamapps").join("libraryfolders.vdf"), vdf).unwrap();
+
+        assert_eq!(find_steam_install(&steam), Some(game_dir));
+    }
+}
@@ -0,0 +1,157 @@
+//! Crate-wide error type
+
+use std::{
+    fmt::Display,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::atomic::WriteError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A file or folder could not be read
+    Io { path: PathBuf, source: io::Error },
+    /// A CCR mod is not valid TOML
+    Toml {
+        path: PathBuf,
+        line: Option<usize>,
+        source: Box<toml::de::Error>,
+    },
+    /// The ini is missing or can't be used
+    Ini {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A command in a mod file is invalid
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A user GMST database is invalid
+    Database {
+        path: PathBuf,
+        line: Option<usize>,
+        message: String,
+    },
+    /// A file could not be written
+    Write(WriteError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    /// Creates a TOML error, with the line of the error in the given text
+    pub fn toml(path: &Path, text: &str, source: toml::de::Error) -> Self {
+        Error::Toml {
+            path: path.to_owned(),
+            line: get_toml_line(text, &source),
+            source: Box::new(source),
+        }
+    }
+
+    /// The file the error is about
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::Io { path, .. }
+            | Error::Toml { path, .. }
+            | Error::Ini { path, .. }
+            | Error::Parse { path, .. }
+            | Error::Database { path, .. } => path,
+            Error::Write(err) => &err.path,
+        }
+    }
+}
+
+/// Gets the 1-based line of a TOML error in the given text
+pub fn get_toml_line(text: &str, err: &toml::de::Error) -> Option<usize> {
+    err.span().map(|span| get_line_number(text, span.start))
+}
+
+/// Gets the 1-based line number of a byte offset in a text
+pub fn get_line_number(text: &str, offset: usize) -> usize {
+    let offset = offset.min(text.len());
+    text.as_bytes()[..offset]
+        .iter()
+        .filter(|b| **b == b'\n')
+        .count()
+        + 1
+}
+
+/// Formats a path with an optional line number, like path:line
+fn location(path: &Path, line: &Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{}", path.display(), line),
+        None => path.display().to_string(),
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io { path, source } => {
+                write!(f, "Failed to read {}: {}", path.display(), source)
+            }
+            Error::Toml { path, line, source } => {
+                write!(
+                    f,
+                    "Invalid TOML in {}: {}",
+                    location(path, line),
+                    source.message()
+                )
+            }
+            Error::Ini {
+                path,
+                line,
+                message,
+            } => write!(f, "Invalid ini {}: {}", location(path, line), message),
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(
+                f,
+                "Invalid command in {}: {}",
+                location(path, line),
+                message
+            ),
+            Error::Database {
+                path,
+                line,
+                message,
+            } => write!(
+                f,
+                "Invalid GMST database {}: {}",
+                location(path, line),
+                message
+            ),
+            Error::Write(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Toml { source, .. } => Some(source.as_ref()),
+            Error::Ini { .. } | Error::Parse { .. } | Error::Database { .. } => None,
+            Error::Write(err) => Some(err),
+        }
+    }
+}
+
+impl From<WriteError> for Error {
+    fn from(err: WriteError) -> Self {
+        Error::Write(err)
+    }
+}
@@ -0,0 +1,233 @@
+//! Groups of the GMST tree view, by section, name family or metadata category
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::GmstViewModel;
+
+/// Families with more GMSTs than this are split by their first two words
+const FAMILY_SPLIT_SIZE: usize = 100;
+const OTHER_GROUP: &str = "Other";
+
+/// How the GMST grid is grouped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+pub enum EGmstGrouping {
+    /// A flat list sorted by name
+    #[default]
+    None,
+    /// By the :Section suffix
+    Section,
+    /// By the first words of the name, e.g. fCombatCover
+    Family,
+    /// By the metadata category
+    Category,
+}
+
+impl Display for EGmstGrouping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            EGmstGrouping::None => "None",
+            EGmstGrouping::Section => "Section",
+            EGmstGrouping::Family => "Name family",
+            EGmstGrouping::Category => "Category",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A group of the GMST tree
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GmstGroup {
+    pub name: String,
+    pub children: Vec<GmstGroup>,
+    /// Indices into the GMST view models, sorted by name
+    pub gmsts: Vec<usize>,
+}
+
+impl GmstGroup {
+    /// All GMSTs in this group and its children
+    pub fn get_all_gmsts(&self) -> Vec<usize> {
+        let mut gmsts = self.gmsts.clone();
+        for child in &self.children {
+            gmsts.extend(child.get_all_gmsts());
+        }
+        gmsts
+    }
+}
+
+/// Splits a GMST name into its CamelCase words, without the type prefix and section
+/// e.g. fAIAimBlockedHalfCircleRadius:Combat -> AI, Aim, Blocked, Half, Circle, Radius
+pub fn get_name_words(name: &str) -> Vec<&str> {
+    let name = name.split(':').next().unwrap_or_default();
+    let Some(first) = name.chars().next() else {
+        return vec![];
+    };
+    let name = &name[first.len_utf8()..];
+
+    let chars = name.char_indices().collect::<Vec<_>>();
+    let mut words = vec![];
+    let mut start = 0;
+    for i in 1..chars.len() {
+        let (idx, c) = chars[i];
+        let prev = chars[i - 1].1;
+        let next_is_lower = matches!(chars.get(i + 1), Some((_, n)) if n.is_lowercase());
+        // a new word starts at an upper case letter after a lower case one,
+        // or at the last upper case letter of an acronym, e.g. AIAim -> AI, Aim
+        let is_start = c.is_uppercase()
+            && (prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower));
+        if is_start {
+            words.push(&name[start..idx]);
+            start = idx;
+        }
+    }
+    if start < name.len() {
+        words.push(&name[start..]);
+    }
+    words
+}
+
+/// Groups the GMSTs, GMSTs stay sorted by name within a group
+pub fn group_gmsts(gmst_vms: &[GmstViewModel], grouping: EGmstGrouping) -> Vec<GmstGroup> {
+    match grouping {
+        EGmstGrouping::None => vec![GmstGroup {
+            name: String::new(),
+            children: vec![],
+            gmsts: (0..gmst_vms.len()).collect(),
+        }],
+        EGmstGrouping::Section => {
+            let mut groups = group_by(gmst_vms.iter().enumerate(), |vm| {
+                vm.name.section.as_ref().map(|s| s.to_owned())
+            });
+            // GMSTs without a section first
+            if let Some(idx) = groups.iter().position(|g| g.name.is_empty()) {
+                let mut group = groups.remove(idx);
+                group.name = "No section".to_owned();
+                groups.insert(0, group);
+            }
+            groups
+        }
+        EGmstGrouping::Category => {
+            let mut groups = group_by(gmst_vms.iter().enumerate(), |vm| {
+                vm.metadata
+                    .as_ref()
+                    .and_then(|m| m.category)
+                    .map(|c| c.to_string())
+            });
+            if let Some(idx) = groups.iter().position(|g| g.name.is_empty()) {
+                let mut group = groups.remove(idx);
+                group.name = "Uncategorized".to_owned();
+                groups.push(group);
+            }
+            groups
+        }
+        EGmstGrouping::Family => get_family_groups(gmst_vms),
+    }
+}
+
+/// Groups by a key, case-insensitive and sorted by key, GMSTs without a key get an empty name
+fn group_by<'a>(
+    gmst_vms: impl Iterator<Item = (usize, &'a GmstViewModel)>,
+    key: impl Fn(&GmstViewModel) -> Option<String>,
+) -> Vec<GmstGroup> {
+    let mut map: BTreeMap<String, GmstGroup> = BTreeMap::new();
+    for (idx, vm) in gmst_vms {
+        let name = key(vm).unwrap_or_default();
+        let group = map.entry(name.to_lowercase()).or_insert_with(|| GmstGroup {
+            name,
+            ..Default::default()
+        });
+        group.gmsts.push(idx);
+    }
+    map.into_values().collect()
+}
+
+/// Groups by the first word of the name, large families are split by the first two words
+/// Families with a single GMST are collected in an Other group
+fn get_family_groups(gmst_vms: &[GmstViewModel]) -> Vec<GmstGroup> {
+    let family = |words: &[&str], count: usize| words[..count.min(words.len())].concat();
+    let mut groups = vec![];
+    let mut other = GmstGroup {
+        name: OTHER_GROUP.to_owned(),
+        ..Default::default()
+    };
+    for mut group in group_by(gmst_vms.iter().enumerate(), |vm| {
+        Some(family(&get_name_words(&vm.gmst.name), 1))
+    }) {
+        if group.gmsts.len() == 1 {
+            other.gmsts.extend(group.gmsts);
+            continue;
+        }
+        if group.gmsts.len() > FAMILY_SPLIT_SIZE {
+            let children = group_by(group.gmsts.iter().map(|i| (*i, &gmst_vms[*i])), |vm| {
+                Some(family(&get_name_words(&vm.gmst.name), 2))
+            });
+            group.gmsts = vec![];
+            for child in children {
+                // small sub families stay in the parent
+                if child.gmsts.len() == 1 {
+                    group.gmsts.extend(child.gmsts);
+                } else {
+                    group.children.push(child);
+                }
+            }
+            group
+                .gmsts
+                .sort_by(|a, b| gmst_vms[*a].gmst.name.cmp(&gmst_vms[*b].gmst.name));
+        }
+        groups.push(group);
+    }
+
+    if !other.gmsts.is_empty() {
+        other
+            .gmsts
+            .sort_by(|a, b| gmst_vms[*a].gmst.name.cmp(&gmst_vms[*b].gmst.name));
+        groups.push(other);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::get_vm, EGmstValue};
+
+    #[test]
+    fn splits_name_words() {
+        assert_eq!(
+            get_name_words("fAIAimBlockedHalfCircleRadius"),
+            vec!["AI", "Aim", "Blocked", "Half", "Circle", "Radius"]
+        );
+        assert_eq!(
+            get_name_words("b3rdPersonAimUseWeaponFOV:ThirdPersonCam"),
+            vec!["3rd", "Person", "Aim", "Use", "Weapon", "FOV"]
+        );
+        assert_eq!(get_name_words("fVATSTime"), vec!["VATS", "Time"]);
+        assert_eq!(get_name_words("f"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn groups_gmsts() {
+        let vms = ["bA:Cam", "fAimA", "fAimB", "fB", "iC:cam"]
+            .iter()
+            .map(|name| get_vm(name, EGmstValue::Bool(false)))
+            .collect::<Vec<_>>();
+
+        let groups = group_gmsts(&vms, EGmstGrouping::Section);
+        let names = groups.iter().map(|g| g.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["No section", "Cam"]);
+        assert_eq!(groups[1].gmsts, vec![0, 4]);
+
+        let groups = group_gmsts(&vms, EGmstGrouping::Family);
+        let names = groups.iter().map(|g| g.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Aim", "Other"]);
+        assert_eq!(groups[0].gmsts, vec![1, 2]);
+        assert_eq!(groups[1].gmsts, vec![0, 3, 4]);
+
+        let groups = group_gmsts(&vms, EGmstGrouping::Category);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "Uncategorized");
+    }
+}
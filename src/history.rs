@@ -0,0 +1,290 @@
+//! Undo/redo history of GMST edits and mod list changes
+//!
+//! Every change is stored as a command that knows its old and new state,
+//! so undo and redo only touch what the change touched.
+
+use crate::{EGmstValue, GmstViewModel, ModViewModel};
+
+/// How many changes can be undone
+const MAX_HISTORY: usize = 100;
+
+/// A GMST value before and after a change
+#[derive(Debug, Clone, PartialEq)]
+pub struct GmstChange {
+    pub name: String,
+    pub old: EGmstValue,
+    pub new: EGmstValue,
+}
+
+/// A change that can be undone
+#[derive(Debug, Clone, PartialEq)]
+pub enum EHistoryCommand {
+    /// GMST values were edited or reset
+    SetGmsts(Vec<GmstChange>),
+    /// The values of a mod were shown in the grid or hidden again
+    ToggleOverlay {
+        mod_name: String,
+        is_ccr: bool,
+        enabled: bool,
+        gmsts: Vec<GmstChange>,
+    },
+    /// A bat mod was enabled or disabled
+    SetModEnabled { mod_name: String, enabled: bool },
+    /// The bat mods were reordered, by mod name
+    ReorderMods {
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+}
+
+impl EHistoryCommand {
+    /// A short text for the history panel
+    pub fn get_description(&self) -> String {
+        match self {
+            EHistoryCommand::SetGmsts(changes) => match changes.as_slice() {
+                [change] => format!("Set {} to {}", change.name, change.new),
+                changes => format!("Edit {} GMSTs", changes.len()),
+            },
+            EHistoryCommand::ToggleOverlay {
+                mod_name, enabled, ..
+            } => match enabled {
+                true => format!("Show {}", mod_name),
+                false => format!("Hide {}", mod_name),
+            },
+            EHistoryCommand::SetModEnabled { mod_name, enabled } => match enabled {
+                true => format!("Enable {}", mod_name),
+                false => format!("Disable {}", mod_name),
+            },
+            EHistoryCommand::ReorderMods { .. } => "Reorder mods".to_owned(),
+        }
+    }
+
+    /// Applies the command, or reverts it if undo is set
+    fn apply(
+        &self,
+        undo: bool,
+        gmst_vms: &mut [GmstViewModel],
+        mods: Option<&mut Vec<ModViewModel>>,
+        ccr_mods: Option<&mut Vec<ModViewModel>>,
+    ) {
+        match self {
+            EHistoryCommand::SetGmsts(changes) => set_gmsts(changes, undo, gmst_vms),
+            EHistoryCommand::ToggleOverlay {
+                mod_name,
+                is_ccr,
+                enabled,
+                gmsts,
+            } => {
+                set_gmsts(gmsts, undo, gmst_vms);
+                let shown = *enabled != undo;
+                for change in gmsts {
+                    if let Some(vm) = gmst_vms.iter_mut().find(|vm| vm.gmst.name == change.name) {
+                        vm.overlay = shown.then(|| vm.gmst.value.clone());
+                    }
+                }
+                let mods = if *is_ccr { ccr_mods } else { mods };
+                if let Some(mod_vm) = mods.and_then(|m| m.iter_mut().find(|m| &m.name == mod_name))
+                {
+                    mod_vm.overlay_enabled = shown;
+                }
+            }
+            EHistoryCommand::SetModEnabled { mod_name, enabled } => {
+                if let Some(mod_vm) = mods.and_then(|m| m.iter_mut().find(|m| &m.name == mod_name))
+                {
+                    mod_vm.enabled = *enabled != undo;
+                }
+            }
+            EHistoryCommand::ReorderMods { before, after } => {
+                let order = if undo { before } else { after };
+                if let Some(mods) = mods {
+                    // mods that were added since keep their place at the end
+                    mods.sort_by_key(|m| {
+                        order
+                            .iter()
+                            .position(|n| n == &m.name)
+                            .unwrap_or(usize::MAX)
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn set_gmsts(changes: &[GmstChange], undo: bool, gmst_vms: &mut [GmstViewModel]) {
+    for change in changes {
+        if let Some(vm) = gmst_vms.iter_mut().find(|vm| vm.gmst.name == change.name) {
+            vm.gmst.value = match undo {
+                true => change.old.clone(),
+                false => change.new.clone(),
+            };
+        }
+    }
+}
+
+/// Gets the GMSTs whose values differ from a snapshot of the grid
+pub fn get_gmst_changes(before: &[EGmstValue], gmst_vms: &[GmstViewModel]) -> Vec<GmstChange> {
+    before
+        .iter()
+        .zip(gmst_vms)
+        .filter(|(old, vm)| **old != vm.gmst.value)
+        .map(|(old, vm)| GmstChange {
+            name: vm.gmst.name.to_owned(),
+            old: old.clone(),
+            new: vm.gmst.value.clone(),
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<EHistoryCommand>,
+    redo: Vec<EHistoryCommand>,
+    /// The GMST that is being dragged or typed in, its edits are merged into one command
+    editing: Option<String>,
+}
+
+impl History {
+    /// Adds a change that was already applied
+    pub fn push(&mut self, command: EHistoryCommand) {
+        self.editing = None;
+        self.redo.clear();
+        self.undo.push(command);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Adds an edit of a GMST value, continues the last edit while the same GMST is edited
+    pub fn push_edit(&mut self, name: &str, old: EGmstValue, new: EGmstValue) {
+        if self.editing.as_deref() == Some(name) {
+            if let Some(EHistoryCommand::SetGmsts(changes)) = self.undo.last_mut() {
+                if let [change] = changes.as_mut_slice() {
+                    change.new = new;
+                    return;
+                }
+            }
+        }
+        self.push(EHistoryCommand::SetGmsts(vec![GmstChange {
+            name: name.to_owned(),
+            old,
+            new,
+        }]));
+        self.editing = Some(name.to_owned());
+    }
+
+    /// Checks if a GMST is being edited
+    pub fn is_editing(&self, name: &str) -> bool {
+        self.editing.as_deref() == Some(name)
+    }
+
+    /// Ends the current edit, the next edit is a new command
+    pub fn end_edit(&mut self) {
+        self.editing = None;
+    }
+
+    /// Removes all changes, e.g. when the grid is replaced
+    pub fn clear(&mut self) {
+        *self = History::default();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// The changes that can be undone, oldest first
+    pub fn get_undo_commands(&self) -> &[EHistoryCommand] {
+        &self.undo
+    }
+
+    /// The changes that can be redone, next first
+    pub fn get_redo_commands(&self) -> impl Iterator<Item = &EHistoryCommand> {
+        self.redo.iter().rev()
+    }
+
+    /// Reverts the last change, returns it
+    pub fn undo(
+        &mut self,
+        gmst_vms: &mut [GmstViewModel],
+        mods: Option<&mut Vec<ModViewModel>>,
+        ccr_mods: Option<&mut Vec<ModViewModel>>,
+    ) -> Option<&EHistoryCommand> {
+        self.editing = None;
+        let command = self.undo.pop()?;
+        command.apply(true, gmst_vms, mods, ccr_mods);
+        self.redo.push(command);
+        self.redo.last()
+    }
+
+    /// Applies the last undone change again, returns it
+    pub fn redo(
+        &mut self,
+        gmst_vms: &mut [GmstViewModel],
+        mods: Option<&mut Vec<ModViewModel>>,
+        ccr_mods: Option<&mut Vec<ModViewModel>>,
+    ) -> Option<&EHistoryCommand> {
+        self.editing = None;
+        let command = self.redo.pop()?;
+        command.apply(false, gmst_vms, mods, ccr_mods);
+        self.undo.push(command);
+        self.undo.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::get_vm;
+
+    #[test]
+    fn merges_edits() {
+        let mut vms = vec![get_vm("fA", EGmstValue::Float(3.0))];
+        let mut history = History::default();
+        history.push_edit("fA", EGmstValue::Float(1.0), EGmstValue::Float(2.0));
+        history.push_edit("fA", EGmstValue::Float(2.0), EGmstValue::Float(3.0));
+        assert_eq!(history.get_undo_commands().len(), 1);
+
+        history.undo(&mut vms, None, None);
+        assert_eq!(vms[0].gmst.value, EGmstValue::Float(1.0));
+        assert!(!history.can_undo());
+
+        history.redo(&mut vms, None, None);
+        assert_eq!(vms[0].gmst.value, EGmstValue::Float(3.0));
+
+        history.end_edit();
+        history.push_edit("fA", EGmstValue::Float(3.0), EGmstValue::Float(4.0));
+        assert_eq!(history.get_undo_commands().len(), 2);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undoes_mod_changes() {
+        let mut vms = vec![];
+        let mut mods = vec![
+            ModViewModel::from_command("a"),
+            ModViewModel::from_command("b"),
+        ];
+        let mut history = History::default();
+
+        mods[0].enabled = false;
+        history.push(EHistoryCommand::SetModEnabled {
+            mod_name: "a".to_owned(),
+            enabled: false,
+        });
+        mods.reverse();
+        history.push(EHistoryCommand::ReorderMods {
+            before: vec!["a".to_owned(), "b".to_owned()],
+            after: vec!["b".to_owned(), "a".to_owned()],
+        });
+
+        history.undo(&mut vms, Some(&mut mods), None);
+        assert_eq!(mods[0].name, "a");
+        history.undo(&mut vms, Some(&mut mods), None);
+        assert!(mods[0].enabled);
+        history.redo(&mut vms, Some(&mut mods), None);
+        assert!(!mods[0].enabled);
+    }
+}
@@ -0,0 +1,313 @@
+//! Lossless model of Bethesda ini files like StarfieldCustom.ini
+//!
+//! Every line keeps its original text and line ending, so writing an unchanged
+//! ini reproduces the input byte for byte. Only lines changed with [`Ini::set`] are rewritten.
+
+use std::fmt::Display;
+
+const BOM: char = '\u{feff}';
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EIniLine {
+    Blank,
+    /// A line starting with ; or #
+    Comment,
+    /// [Section]
+    Section(String),
+    /// key=value
+    Entry {
+        key: String,
+        value: String,
+    },
+    /// Anything else, ignored by the game
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IniLine {
+    pub kind: EIniLine,
+    /// Original text without the line ending
+    pub text: String,
+    /// "\r\n", "\n" or "" for the last line
+    pub ending: String,
+}
+
+impl IniLine {
+    fn parse(text: &str, ending: &str) -> Self {
+        let trimmed = text.trim();
+        let kind = if trimmed.is_empty() {
+            EIniLine::Blank
+        } else if trimmed.starts_with(';') || trimmed.starts_with('#') {
+            EIniLine::Comment
+        } else if let Some(name) = trimmed.strip_prefix('[').and_then(|p| p.strip_suffix(']')) {
+            EIniLine::Section(name.trim().to_owned())
+        } else if let Some((key, value)) = trimmed.split_once('=') {
+            EIniLine::Entry {
+                key: key.trim().to_owned(),
+                value: value.trim().to_owned(),
+            }
+        } else {
+            EIniLine::Invalid
+        };
+
+        IniLine {
+            kind,
+            text: text.to_owned(),
+            ending: ending.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ini {
+    /// The file starts with a UTF-8 byte order mark
+    pub bom: bool,
+    pub lines: Vec<IniLine>,
+}
+
+impl Ini {
+    pub fn parse(text: &str) -> Self {
+        let (bom, text) = match text.strip_prefix(BOM) {
+            Some(stripped) => (true, stripped),
+            None => (false, text),
+        };
+
+        let mut lines = vec![];
+        for raw in text.split_inclusive('\n') {
+            let (line, ending) = if let Some(line) = raw.strip_suffix("\r\n") {
+                (line, "\r\n")
+            } else if let Some(line) = raw.strip_suffix('\n') {
+                (line, "\n")
+            } else {
+                (raw, "")
+            };
+            lines.push(IniLine::parse(line, ending));
+        }
+
+        Ini { bom, lines }
+    }
+
+    /// Gets the section each line belongs to, None for lines before the first section
+    pub fn sections(&self) -> Vec<Option<&str>> {
+        let mut current = None;
+        self.lines
+            .iter()
+            .map(|line| {
+                if let EIniLine::Section(name) = &line.kind {
+                    current = Some(name.as_str());
+                }
+                current
+            })
+            .collect()
+    }
+
+    /// Finds the line index of a key in a section, ignoring case like the game does
+    /// The game reads the last line if a key is set more than once
+    pub fn find(&self, section: &str, key: &str) -> Option<usize> {
+        self.sections()
+            .into_iter()
+            .zip(self.lines.iter())
+            .rposition(|(s, line)| match (s, &line.kind) {
+                (Some(s), EIniLine::Entry { key: k, .. }) => {
+                    s.eq_ignore_ascii_case(section) && k.eq_ignore_ascii_case(key)
+                }
+                _ => false,
+            })
+    }
+
+    /// Gets the value of a key in a section
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        match &self.lines[self.find(section, key)?].kind {
+            EIniLine::Entry { value, .. } => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The line ending used by this file, CRLF for new files
+    pub fn line_ending(&self) -> &str {
+        self.lines
+            .iter()
+            .map(|l| l.ending.as_str())
+            .find(|e| !e.is_empty())
+            .unwrap_or("\r\n")
+    }
+
+    /// Sets the value of a key in a section
+    /// Only the line of that key is changed. Missing keys are added to the end of the section,
+    /// missing sections to the end of the file
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        // update in place
+        if let Some(idx) = self.find(section, key) {
+            let line = &mut self.lines[idx];
+            if let (EIniLine::Entry { key: old_key, .. }, Some(eq)) =
+                (&line.kind, line.text.find('='))
+            {
+                // keep the indentation, the spelling of the key and the spaces around =
+                let old_value = &line.text[eq + 1..];
+                let value_start = eq + 1 + old_value.len() - old_value.trim_start().len();
+                line.kind = EIniLine::Entry {
+                    key: old_key.to_owned(),
+                    value: value.to_owned(),
+                };
+                line.text = format!("{}{}", &line.text[..value_start], value);
+            }
+            return;
+        }
+
+        let text = format!("{}={}", key, value);
+        let kind = EIniLine::Entry {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        };
+        let ending = self.line_ending().to_owned();
+        let section_start = self.lines.iter().position(
+            |l| matches!(&l.kind, EIniLine::Section(name) if name.eq_ignore_ascii_case(section)),
+        );
+
+        let insert_at = match section_start {
+            // after the last non blank line of the section
+            Some(start) => {
+                let mut insert_at = start + 1;
+                for (idx, line) in self.lines.iter().enumerate().skip(start + 1) {
+                    match line.kind {
+                        EIniLine::Section(_) => break,
+                        EIniLine::Blank => {}
+                        _ => insert_at = idx + 1,
+                    }
+                }
+                insert_at
+            }
+            // append a new section
+            None => {
+                if let Some(last) = self.lines.last_mut() {
+                    if last.ending.is_empty() {
+                        last.ending = ending.to_owned();
+                    }
+                    if last.kind != EIniLine::Blank {
+                        self.lines.push(IniLine::parse("", &ending));
+                    }
+                }
+                self.lines
+                    .push(IniLine::parse(&format!("[{}]", section), &ending));
+                self.lines.len()
+            }
+        };
+
+        // the previous line needs a line ending
+        if insert_at > 0 && self.lines[insert_at - 1].ending.is_empty() {
+            self.lines[insert_at - 1].ending = ending.to_owned();
+        }
+        self.lines.insert(insert_at, IniLine { kind, text, ending });
+    }
+}
+
+impl Display for Ini {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.bom {
+            write!(f, "{}", BOM)?;
+        }
+        for line in &self.lines {
+            write!(f, "{}{}", line.text, line.ending)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM_INI: &str = "; my ini\r\n\r\n[Display]\r\nfGamma = 1.2\r\n  bFull Screen=1\r\n\r\n[General]\r\nsStartingConsoleCommand=bat a;bat b;\r\nbInvalidateOlderFiles=1\r\n\r\n[Archive]\r\nsResourceDataDirsFinal=\r\ngarbage line\r\n";
+
+    #[test]
+    fn round_trips_unchanged() {
+        for text in [
+            CUSTOM_INI,
+            "",
+            "\n",
+            "[General]\nsStartingConsoleCommand=bat a",
+            "[General]\r\nkey=1\nkey2=2\r\n",
+            "\u{feff}[General]\r\nbFoo=1\r\n",
+            "no section=1\n\n\n# hash comment\n[ Spaced ]\n",
+        ] {
+            assert_eq!(Ini::parse(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn parses_sections_and_entries() {
+        let ini = Ini::parse(CUSTOM_INI);
+        assert_eq!(
+            ini.get("General", "sStartingConsoleCommand"),
+            Some("bat a;bat b;")
+        );
+        assert_eq!(ini.get("display", "FGAMMA"), Some("1.2"));
+        assert_eq!(ini.get("Display", "bFull Screen"), Some("1"));
+        assert_eq!(ini.get("Archive", "sResourceDataDirsFinal"), Some(""));
+        assert_eq!(ini.get("Display", "sStartingConsoleCommand"), None);
+        assert_eq!(ini.lines[12].kind, EIniLine::Invalid);
+        assert!(!ini.bom);
+    }
+
+    #[test]
+    fn set_updates_only_that_line() {
+        let mut ini = Ini::parse(CUSTOM_INI);
+        ini.set("General", "sstartingconsolecommand", "bat c;");
+        assert_eq!(
+            ini.to_string(),
+            CUSTOM_INI.replace("bat a;bat b;", "bat c;")
+        );
+    }
+
+    #[test]
+    fn set_keeps_indentation_and_updates_last_key() {
+        let text = "[General]\r\n  sStartingConsoleCommand = bat a;\r\nbFoo=1\r\n\t SSTARTINGCONSOLECOMMAND =bat b;\r\n";
+        let mut ini = Ini::parse(text);
+        assert_eq!(
+            ini.get("General", "sStartingConsoleCommand"),
+            Some("bat b;")
+        );
+
+        ini.set("General", "sStartingConsoleCommand", "bat c;");
+        let changed = text.replace("=bat b;", "=bat c;");
+        assert_eq!(ini.to_string(), changed);
+        assert_eq!(Ini::parse(&changed), ini);
+    }
+
+    #[test]
+    fn set_adds_key_to_existing_section() {
+        let mut ini = Ini::parse("[General]\r\nbFoo=1\r\n\r\n[Display]\r\nfGamma=1\r\n");
+        ini.set("General", "sStartingConsoleCommand", "bat a;");
+        assert_eq!(
+            ini.to_string(),
+            "[General]\r\nbFoo=1\r\nsStartingConsoleCommand=bat a;\r\n\r\n[Display]\r\nfGamma=1\r\n"
+        );
+    }
+
+    #[test]
+    fn set_adds_missing_section() {
+        let mut ini = Ini::parse("[Display]\nfGamma=1\nsStartingConsoleCommand=bat old;");
+        ini.set("General", "sStartingConsoleCommand", "bat a;");
+        assert_eq!(
+            ini.to_string(),
+            "[Display]\nfGamma=1\nsStartingConsoleCommand=bat old;\n\n[General]\nsStartingConsoleCommand=bat a;\n"
+        );
+
+        let mut ini = Ini::parse("");
+        ini.set("General", "sStartingConsoleCommand", "bat a;");
+        assert_eq!(
+            ini.to_string(),
+            "[General]\r\nsStartingConsoleCommand=bat a;\r\n"
+        );
+    }
+
+    #[test]
+    fn set_finds_section_not_at_line_start() {
+        let mut ini = Ini::parse("  [General]  \nsStartingConsoleCommand=bat a;\n");
+        ini.set("General", "sStartingConsoleCommand", "bat b;");
+        assert_eq!(
+            ini.to_string(),
+            "  [General]  \nsStartingConsoleCommand=bat b;\n"
+        );
+    }
+}
@@ -1,28 +1,59 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod app;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod atomic;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod backup;
+pub mod console;
+pub mod database;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod discovery;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod error;
+pub mod grouping;
+pub mod history;
+pub mod ini;
+pub mod metadata;
+pub mod profile;
+#[cfg(test)]
+mod test_util;
 pub use app::TemplateApp;
+use database::EGmstSource;
+use metadata::GmstMetadata;
 use serde::{Deserialize, Serialize};
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
-    fs::File,
-    io::{self, BufRead, Write},
     path::PathBuf,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::{io, path::Path};
+
+#[cfg(not(target_arch = "wasm32"))]
+use atomic::{write_atomic, EWriteStage, WriteError};
+#[cfg(not(target_arch = "wasm32"))]
+use backup::BackupSettings;
+#[cfg(not(target_arch = "wasm32"))]
+use console::{ConsoleCommand, ConsoleFile, ConsoleLine, ECommand};
 #[cfg(not(target_arch = "wasm32"))]
 use directories::UserDirs;
 #[cfg(not(target_arch = "wasm32"))]
+use error::{Error, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use ini::{EIniLine, Ini};
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs::read_dir;
 
-#[derive(Clone, Copy, serde::Deserialize, serde::Serialize, PartialEq)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, PartialEq)]
 pub enum EGmstValue {
     Bool(bool),
     Float(f32),
     Int(i32),
     UInt(u32),
+    String(String),
 }
 
 impl Display for EGmstValue {
@@ -35,6 +66,7 @@ impl Display for EGmstValue {
             EGmstValue::Float(f) => f.to_string(),
             EGmstValue::Int(i) => i.to_string(),
             EGmstValue::UInt(u) => u.to_string(),
+            EGmstValue::String(s) => format!("\"{}\"", s),
         };
         write!(f, "{}", s)
     }
@@ -46,16 +78,164 @@ pub struct Gmst {
     pub value: EGmstValue,
 }
 
+/// A GMST name, optionally qualified with the section of its setting collection as in name:Section
+/// Names are compared and hashed ignoring case, like the game does, and keep their spelling
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, Default)]
+pub struct GmstName {
+    pub name: String,
+    pub section: Option<String>,
+}
+
+impl GmstName {
+    /// Parses a name, with or without the quotes of a console argument
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        let text = text
+            .strip_prefix('"')
+            .and_then(|t| t.strip_suffix('"'))
+            .unwrap_or(text);
+        match text.split_once(':') {
+            Some((name, section)) => GmstName {
+                name: name.to_owned(),
+                section: Some(section.to_owned()),
+            },
+            None => GmstName {
+                name: text.to_owned(),
+                section: None,
+            },
+        }
+    }
+}
+
+impl PartialEq for GmstName {
+    fn eq(&self, other: &Self) -> bool {
+        self.name.eq_ignore_ascii_case(&other.name)
+            && match (&self.section, &other.section) {
+                (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+impl Eq for GmstName {}
+
+impl std::hash::Hash for GmstName {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // must agree with eq, so the lowercase bytes are hashed
+        for b in self.name.bytes() {
+            state.write_u8(b.to_ascii_lowercase());
+        }
+        match &self.section {
+            Some(section) => {
+                state.write_u8(b':');
+                for b in section.bytes() {
+                    state.write_u8(b.to_ascii_lowercase());
+                }
+            }
+            None => state.write_u8(0xff),
+        }
+    }
+}
+
+impl Ord for GmstName {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // must agree with eq, so the lowercase bytes are compared
+        let cmp_lowercase = |a: &str, b: &str| {
+            a.bytes()
+                .map(|b| b.to_ascii_lowercase())
+                .cmp(b.bytes().map(|b| b.to_ascii_lowercase()))
+        };
+        cmp_lowercase(&self.name, &other.name).then_with(|| match (&self.section, &other.section) {
+            (Some(a), Some(b)) => cmp_lowercase(a, b),
+            (a, b) => a.is_some().cmp(&b.is_some()),
+        })
+    }
+}
+
+impl PartialOrd for GmstName {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for GmstName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.section {
+            Some(section) => write!(f, "{}:{}", self.name, section),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Finds the spelling of GMSTs in a list of known names, ignoring case and quotes
+pub struct GmstNameIndex<'a> {
+    names: HashMap<GmstName, &'a String>,
+    /// Names by their lowercase base name, to suggest the GMST of another section
+    base_names: HashMap<String, &'a String>,
+}
+
+impl<'a> GmstNameIndex<'a> {
+    /// Indexes the names, of names that only differ in case the smallest is kept
+    pub fn new(names: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut index = GmstNameIndex {
+            names: HashMap::default(),
+            base_names: HashMap::default(),
+        };
+        for known in names {
+            let name = GmstName::parse(known);
+            let base = name.name.to_lowercase();
+            index
+                .names
+                .entry(name)
+                .and_modify(|e| *e = known.min(*e))
+                .or_insert(known);
+            index
+                .base_names
+                .entry(base)
+                .and_modify(|e| *e = known.min(*e))
+                .or_insert(known);
+        }
+        index
+    }
+
+    /// Gets the known spelling of a GMST
+    pub fn get(&self, name: &GmstName) -> Option<&'a String> {
+        self.names.get(name).copied()
+    }
+
+    /// Gets the known spelling of a GMST as written in a mod or database
+    pub fn find(&self, name: &str) -> Option<&'a String> {
+        self.get(&GmstName::parse(name))
+    }
+
+    /// Gets a known GMST with the same base name, in any section
+    pub fn find_base(&self, name: &GmstName) -> Option<&'a String> {
+        self.base_names.get(&name.name.to_lowercase()).copied()
+    }
+}
+
 #[derive(serde::Deserialize, serde::Serialize, PartialEq)]
 pub struct GmstViewModel {
     pub gmst: Gmst,
+    /// The name split into base name and section
+    pub name: GmstName,
     pub is_edited: bool,
+    /// The value shown from a mod with "Toggle show", it is not an edit of the profile
+    #[serde(skip)]
+    pub overlay: Option<EGmstValue>,
+    /// Description and sane range, if known
+    pub metadata: Option<GmstMetadata>,
+    /// The databases that agree on the default value
+    pub sources: Vec<EGmstSource>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, PartialEq, Hash, Clone)]
 pub enum EModType {
     BatMod,
     CcrMod,
+    /// Any other command in sStartingConsoleCommand, e.g. from other tools
+    Command,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, PartialEq, Hash, Clone)]
@@ -68,8 +248,133 @@ pub struct ModViewModel {
     pub overlay_enabled: bool,
     pub gmsts: Vec<String>,
     pub txt: Option<String>,
+    /// Problems found in txt
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ModViewModel {
+    /// Creates a view model for a foreign command in sStartingConsoleCommand
+    pub fn from_command(command: &str) -> Self {
+        ModViewModel {
+            mod_type: EModType::Command,
+            path: PathBuf::new(),
+            name: command.to_owned(),
+            enabled: true,
+            overlay_enabled: false,
+            gmsts: vec![],
+            txt: None,
+            diagnostics: vec![],
+        }
+    }
+
+    /// Gets the sStartingConsoleCommand entry of an enabled bat mod or command
+    pub fn to_start_command(&self) -> Option<EStartCommand> {
+        if !self.enabled {
+            return None;
+        }
+        match self.mod_type {
+            EModType::BatMod => Some(EStartCommand::Bat(
+                self.name
+                    .strip_suffix(".txt")
+                    .unwrap_or(&self.name)
+                    .to_owned(),
+            )),
+            EModType::Command => Some(EStartCommand::Other(self.name.to_owned())),
+            EModType::CcrMod => None,
+        }
+    }
+
+    /// Reads the mod file into txt and checks it for invalid lines
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_text(&mut self, defaults: &HashMap<String, EGmstValue>) -> Result<()> {
+        let txt = std::fs::read_to_string(&self.path).map_err(|err| Error::io(&self.path, err))?;
+        self.diagnostics = get_diagnostics(&txt, self.mod_type == EModType::CcrMod, defaults);
+        self.txt = Some(txt);
+        Ok(())
+    }
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Hash, Clone, Copy)]
+pub enum EDiagnosticLevel {
+    /// The line is valid but not edited by this app
+    Info,
+    /// The line is broken or has no effect
+    Warning,
 }
 
+/// A problem in a line of a mod file
+#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Hash, Clone)]
+pub struct Diagnostic {
+    /// 1-based line number, if known
+    pub line: Option<usize>,
+    pub level: EDiagnosticLevel,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// An entry of sStartingConsoleCommand
+#[derive(Debug, PartialEq, Clone)]
+pub enum EStartCommand {
+    /// bat <name>
+    Bat(String),
+    /// Any other console command
+    Other(String),
+}
+
+impl Display for EStartCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EStartCommand::Bat(name) => write!(f, "bat {}", console::quote_arg(name)),
+            EStartCommand::Other(command) => write!(f, "{}", command),
+        }
+    }
+}
+
+/// Parses the value of sStartingConsoleCommand into its entries
+pub fn parse_start_command(value: &str) -> Vec<EStartCommand> {
+    value
+        .split(';')
+        .map(|arg| arg.trim())
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| match console::tokenize(arg).0.as_slice() {
+            [command, name] if command.text.eq_ignore_ascii_case("bat") => {
+                EStartCommand::Bat(name.text.to_owned())
+            }
+            _ => EStartCommand::Other(arg.to_owned()),
+        })
+        .collect()
+}
+
+/// Builds the value of sStartingConsoleCommand from its entries
+pub fn get_start_command(entries: &[EStartCommand]) -> String {
+    entries.iter().map(|e| format!("{};", e)).collect()
+}
+
+/// A GMST that is set by more than one mod
+#[derive(PartialEq, Clone)]
+pub struct GmstConflict {
+    /// The spelling of the first mod that sets the GMST
+    pub name: GmstName,
+    /// Mod names and the values they set, in load order
+    pub values: Vec<(String, EGmstValue)>,
+    /// The mod whose value is used in game, if any of the mods is active
+    pub winner: Option<String>,
+}
+
+/// The ini section and key of the starting console command
+#[cfg(not(target_arch = "wasm32"))]
+const INI_SECTION: &str = "General";
+#[cfg(not(target_arch = "wasm32"))]
+const INI_START_COMMAND: &str = "sStartingConsoleCommand";
+
 /// Catpuccino themes
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub enum ETheme {
@@ -87,6 +392,40 @@ pub enum ETheme {
 //     }
 // }
 
+/// Section filter of the GMST grid
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ESectionFilter {
+    #[default]
+    All,
+    /// GMSTs without a section
+    None,
+    Section(String),
+}
+
+impl ESectionFilter {
+    pub fn matches(&self, name: &GmstName) -> bool {
+        match self {
+            ESectionFilter::All => true,
+            ESectionFilter::None => name.section.is_none(),
+            ESectionFilter::Section(section) => name
+                .section
+                .as_ref()
+                .map(|s| s.eq_ignore_ascii_case(section))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl Display for ESectionFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ESectionFilter::All => write!(f, "All"),
+            ESectionFilter::None => write!(f, "No section"),
+            ESectionFilter::Section(section) => write!(f, "{}", section),
+        }
+    }
+}
+
 /// App scale
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EScale {
@@ -106,43 +445,20 @@ impl From<EScale> for f32 {
     }
 }
 
-// The output is wrapped in a Result to allow matching on errors
-// Returns an Iterator to the Reader of the lines of the file.
-#[cfg(not(target_arch = "wasm32"))]
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<std::path::Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
-}
-
-fn add_from_bytes(bytes: &[u8], map: &mut HashMap<String, EGmstValue>) {
-    let reader = io::BufReader::new(bytes);
-    reader.lines().for_each(|line| {
-        if let Ok(str) = line {
-            // parse first char
-            let split: Vec<_> = str.split(',').collect();
-            if split.len() == 2 {
-                let name = split[0].trim();
-                let value = split[1].trim();
-                if let Some(parsed) = parse_gmst(name, value) {
-                    map.entry(name.to_owned()).or_insert(parsed);
-                }
-            }
-        }
-    });
-}
-
-fn parse_gmst(name: &str, value: &str) -> Option<EGmstValue> {
-    let first_char: char = name.chars().next().unwrap();
+/// Parses a GMST value from its string representation, based on the name prefix
+pub fn parse_gmst(name: &str, value: &str) -> Option<EGmstValue> {
+    let first_char: char = name
+        .trim_start_matches('"')
+        .chars()
+        .next()?
+        .to_ascii_lowercase();
 
     match first_char {
         'b' => {
-            // parse bool
+            // parse bool, the console also takes 1 and 0
             match value.to_lowercase().as_str() {
-                "true" => Some(true),
-                "false" => Some(false),
+                "true" | "1" => Some(true),
+                "false" | "0" => Some(false),
                 _ => None,
             }
             .map(EGmstValue::Bool)
@@ -171,58 +487,191 @@ fn parse_gmst(name: &str, value: &str) -> Option<EGmstValue> {
                 None
             }
         }
+        // the console can't escape quotes
+        's' if !value.contains('"') => Some(EGmstValue::String(value.to_owned())),
         _ => None,
     }
 }
 
-fn parse_gmsts() -> HashMap<String, EGmstValue> {
-    let mut map: HashMap<String, EGmstValue> = HashMap::default();
-
-    let bytes1 = include_bytes!("Starfield_Game_Settings.csv");
-    add_from_bytes(bytes1, &mut map);
-
-    let bytes2 = include_bytes!("ghidra_gmsts.csv");
-    add_from_bytes(bytes2, &mut map);
+/// Gets the command lines of a mod file and their line numbers
+#[cfg(not(target_arch = "wasm32"))]
+fn get_commands(
+    text: &str,
+    is_ccr: bool,
+) -> std::result::Result<Vec<(ConsoleLine, Option<usize>)>, toml::de::Error> {
+    /// A CCR event with the positions of its commands in the file
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SpannedCcrEvent {
+        // only read to validate the event like CcrModel
+        #[allow(dead_code)]
+        event_type: CCrEEventType,
+        commands: Vec<toml::Spanned<String>>,
+    }
+    #[derive(Deserialize)]
+    struct SpannedCcrModel {
+        event: Vec<SpannedCcrEvent>,
+    }
 
-    map
+    let mut commands: Vec<(ConsoleLine, Option<usize>)> = vec![];
+    if is_ccr {
+        // deserialize toml
+        let res: SpannedCcrModel = toml::from_str(text)?;
+        for event in res.event {
+            for command in event.commands {
+                let line = error::get_line_number(text, command.span().start);
+                commands.push((ConsoleLine::parse(command.get_ref()), Some(line)));
+            }
+        }
+    } else {
+        // ConsoleFile strips the byte order mark
+        for (idx, line) in ConsoleFile::parse(text).lines.into_iter().enumerate() {
+            commands.push((line, Some(idx + 1)));
+        }
+    }
+    Ok(commands)
 }
 
-/// Parse a file for gmsts
+/// Parse a file for gmsts, a GMST written differently keeps its first spelling
+/// Invalid setgs lines are skipped and returned as warnings.
+/// Fails if the file can't be read or a CCR mod is not valid TOML
 #[cfg(not(target_arch = "wasm32"))]
-fn parse_file(path: &PathBuf, is_ccr: bool) -> HashMap<String, EGmstValue> {
-    let mut commands: Vec<String> = vec![];
+pub fn parse_file(
+    path: &Path,
+    is_ccr: bool,
+) -> Result<(HashMap<GmstName, EGmstValue>, Vec<Diagnostic>)> {
+    let text = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    let commands = get_commands(&text, is_ccr).map_err(|err| Error::toml(path, &text, err))?;
 
-    if is_ccr {
-        // deserialize toml
-        if let Ok(file_contents) = std::fs::read_to_string(path) {
-            let res: CcrModel = toml::from_str(file_contents.as_str()).unwrap();
-            for event in res.event {
-                for command in event.commands {
-                    commands.push(command);
+    let mut map: HashMap<GmstName, EGmstValue> = HashMap::default();
+    let mut skipped = vec![];
+    for (console_line, line) in commands {
+        let Some(command) = console_line.command else {
+            continue;
+        };
+        let mut skip = |message: String| {
+            skipped.push(Diagnostic {
+                line,
+                level: EDiagnosticLevel::Warning,
+                message,
+            })
+        };
+
+        match command.kind {
+            ECommand::SetGs { name, value } => {
+                let prefix = name.chars().next().map(|c| c.to_ascii_lowercase());
+                if !matches!(prefix, Some('b' | 'f' | 'i' | 's' | 'u')) {
+                    // not a type we edit
+                    continue;
+                }
+                match parse_gmst(&name, &value) {
+                    Some(parsed_value) => {
+                        map.insert(GmstName::parse(&name), parsed_value);
+                    }
+                    None => skip(get_value_error(&name, &value)),
                 }
             }
+            ECommand::Invalid { usage } if command.name.eq_ignore_ascii_case("setgs") => {
+                skip(format!(
+                    "expected '{}': {}",
+                    usage,
+                    console_line.text.trim()
+                ));
+            }
+            _ => {}
         }
-    } else if let Ok(lines) = read_lines(path) {
-        for line in lines.flatten() {
-            commands.push(line);
+    }
+
+    Ok((map, skipped))
+}
+
+/// Describes why a setgs value can't be parsed for its GMST type
+#[cfg(not(target_arch = "wasm32"))]
+fn get_value_error(name: &str, value: &str) -> String {
+    match name.chars().next().map(|c| c.to_ascii_lowercase()) {
+        Some('b') => format!("{} expects True, False, 1 or 0, got '{}'", name, value),
+        Some('f') => format!("{} expects a number, got '{}'", name, value),
+        Some('i') | Some('u') if value.parse::<f32>().is_ok() => {
+            format!("{} expects an integer, got the float {}", name, value)
         }
+        Some('i') => format!("{} expects an integer, got '{}'", name, value),
+        Some('u') => format!("{} expects a positive integer, got '{}'", name, value),
+        Some('s') => format!("{} can't contain quotes, got '{}'", name, value),
+        _ => format!("{} is not a bool, float, integer or string GMST", name),
     }
+}
 
-    let mut map: HashMap<String, EGmstValue> = HashMap::default();
-    for c in commands {
-        let lline = c.to_lowercase();
-        if lline.starts_with("setgs ") {
-            let splits = &c["setgs ".len()..].split(' ').collect::<Vec<_>>();
-            if splits.len() == 2 {
-                let name = splits[0].trim_matches('"');
-                if let Some(parsed_value) = parse_gmst(name, splits[1]) {
-                    map.insert(name.to_owned(), parsed_value);
-                }
+/// Checks the lines of a mod file, returns warnings for lines that are broken
+/// and infos for lines this app doesn't edit
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_diagnostics(
+    text: &str,
+    is_ccr: bool,
+    defaults: &HashMap<String, EGmstValue>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let names = GmstNameIndex::new(defaults.keys());
+    let commands = match get_commands(text, is_ccr) {
+        Ok(commands) => commands,
+        Err(err) => {
+            return vec![Diagnostic {
+                line: error::get_toml_line(text, &err),
+                level: EDiagnosticLevel::Warning,
+                message: format!("invalid TOML: {}", err.message()),
+            }];
+        }
+    };
+
+    for (console_line, line) in commands {
+        let mut add = |level, message: String| {
+            diagnostics.push(Diagnostic {
+                line,
+                level,
+                message,
+            })
+        };
+
+        let Some(command) = console_line.command else {
+            continue;
+        };
+        let (name, value) = match command.kind {
+            ECommand::SetGs { name, value } => (name, value),
+            ECommand::Invalid { usage } => {
+                add(EDiagnosticLevel::Warning, format!("expected '{}'", usage));
+                continue;
+            }
+            _ => {
+                add(
+                    EDiagnosticLevel::Info,
+                    format!(
+                        "'{}{}' is not a GMST command and is not edited",
+                        command.target.map(|t| t + ".").unwrap_or_default(),
+                        command.name
+                    ),
+                );
+                continue;
+            }
+        };
+
+        let gmst_name = GmstName::parse(&name);
+        if names.get(&gmst_name).is_none() {
+            // the same base name in another section
+            match names.find_base(&gmst_name) {
+                Some(known) => add(
+                    EDiagnosticLevel::Warning,
+                    format!("unknown GMST {}, did you mean {}?", name, known),
+                ),
+                // the databases have no string defaults, so these can't be checked
+                None if name.starts_with(['s', 'S']) => {}
+                None => add(EDiagnosticLevel::Warning, format!("unknown GMST {}", name)),
             }
         }
+        if parse_gmst(&name, &value).is_none() {
+            add(EDiagnosticLevel::Warning, get_value_error(&name, &value));
+        }
     }
 
-    map
+    diagnostics
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -244,47 +693,121 @@ pub struct CcrModel {
     pub event: Vec<CcrEvent>,
 }
 
+/// Checks if a directory is the Starfield base directory
+#[cfg(not(target_arch = "wasm32"))]
+pub fn is_game_dir(path: &Path) -> bool {
+    path.join("Starfield.exe").exists()
+}
+
+/// Tries to find the Starfield base directory
 #[cfg(not(target_arch = "wasm32"))]
-fn get_mods_folder(is_ccr: bool) -> PathBuf {
+pub fn detect_game_dir() -> Option<PathBuf> {
+    // the app may be run from the base dir, e.g. through MO2
+    std::env::current_dir()
+        .ok()
+        .filter(|p| is_game_dir(p))
+        .or_else(discovery::find_game_dir)
+}
+
+/// Gets the mods folder relative to the game directory
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_mods_folder(game_dir: &Path, is_ccr: bool) -> PathBuf {
     if is_ccr {
-        PathBuf::from("")
+        game_dir
             .join("Data")
             .join("SFSE")
             .join("Plugins")
             .join("ConsoleCommandRunner")
     } else {
-        PathBuf::from("")
+        game_dir.to_path_buf()
+    }
+}
+
+/// Checks the file name of a new mod, without extension, returns why it is not valid
+/// The console can't run bat files with spaces, and the name must not clash with an existing mod
+pub fn get_mod_name_error<'a>(
+    name: &str,
+    mod_names: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+    if name.is_empty() {
+        return Some("Enter a name".to_owned());
+    }
+    if name.chars().any(|c| c.is_whitespace()) {
+        return Some("The name can't contain spaces".to_owned());
+    }
+    if name.contains('.') {
+        return Some("The name can't contain dots, the extension is added".to_owned());
     }
+    // the same characters as profile file names, `;` would also split sStartingConsoleCommand
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Some("The name can only contain letters, digits, - and _".to_owned());
+    }
+    // COM0 and LPT0 are reserved on newer Windows versions
+    let is_port = |prefix: &str| {
+        name.len() == 4
+            && name[..3].eq_ignore_ascii_case(prefix)
+            && name.as_bytes()[3].is_ascii_digit()
+    };
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(name))
+        || is_port("COM")
+        || is_port("LPT")
+    {
+        return Some(format!("{} is reserved by Windows", name));
+    }
+    let clashes = mod_names.into_iter().any(|mod_name| {
+        let stem = mod_name
+            .strip_suffix(".txt")
+            .or_else(|| mod_name.strip_suffix(".toml"))
+            .unwrap_or(mod_name);
+        stem.eq_ignore_ascii_case(name)
+    });
+    if clashes {
+        return Some(format!("A mod named {} already exists", name));
+    }
+    None
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn get_mod_file_path(is_ccr: bool, file_name: &str) -> PathBuf {
+pub fn get_mod_file_path(game_dir: &Path, is_ccr: bool, file_name: &str) -> PathBuf {
     if is_ccr {
-        get_mods_folder(is_ccr).join(format!("{}.toml", file_name))
+        get_mods_folder(game_dir, is_ccr).join(format!("{}.toml", file_name))
     } else {
-        get_mods_folder(is_ccr).join(format!("{}.txt", file_name))
+        get_mods_folder(game_dir, is_ccr).join(format!("{}.txt", file_name))
     }
 }
 
 /// Gets all txt file mods in the base dir.
+/// Bat mods are sorted by the load order in the given ini
 #[cfg(not(target_arch = "wasm32"))]
-fn refresh_mods(is_ccr: bool) -> Vec<ModViewModel> {
+pub fn refresh_mods(
+    game_dir: &Path,
+    ini_path: Option<&Path>,
+    is_ccr: bool,
+) -> Result<Vec<ModViewModel>> {
     if is_ccr {
-        refresh_ccr_mods()
+        refresh_ccr_mods(game_dir)
     } else {
-        refresh_bat_mods()
+        refresh_bat_mods(game_dir, ini_path)
     }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn refresh_ccr_mods() -> Vec<ModViewModel> {
+fn refresh_ccr_mods(game_dir: &Path) -> Result<Vec<ModViewModel>> {
     let mut mod_map: Vec<ModViewModel> = vec![];
-    let path = get_mods_folder(true);
+    let path = get_mods_folder(game_dir, true);
     if !path.exists() {
-        return mod_map;
+        return Ok(mod_map);
     }
 
-    for entry in read_dir(path).unwrap().flatten() {
+    for entry in read_dir(&path)
+        .map_err(|err| Error::io(&path, err))?
+        .flatten()
+    {
         let path = entry.path();
         if path.exists() && path.is_file() {
             if let Some(name) = path.file_name() {
@@ -294,11 +817,12 @@ fn refresh_ccr_mods() -> Vec<ModViewModel> {
                         mod_map.push(ModViewModel {
                             mod_type: crate::EModType::CcrMod,
                             path: path.to_owned(),
-                            name: name.to_str().unwrap().into(),
+                            name: name.to_string_lossy().into(),
                             enabled: false,
                             overlay_enabled: false,
                             gmsts: vec![],
                             txt: None,
+                            diagnostics: vec![],
                         });
                     }
                 }
@@ -307,13 +831,17 @@ fn refresh_ccr_mods() -> Vec<ModViewModel> {
     }
 
     mod_map.sort_by_key(|k| k.name.to_owned());
-    mod_map
+    Ok(mod_map)
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn refresh_bat_mods() -> Vec<ModViewModel> {
+fn refresh_bat_mods(game_dir: &Path, ini_path: Option<&Path>) -> Result<Vec<ModViewModel>> {
     let mut mod_map: Vec<ModViewModel> = vec![];
-    for entry in read_dir(get_mods_folder(false)).unwrap().flatten() {
+    let folder = get_mods_folder(game_dir, false);
+    for entry in read_dir(&folder)
+        .map_err(|err| Error::io(&folder, err))?
+        .flatten()
+    {
         let path = entry.path();
         if path.exists() && path.is_file() {
             if let Some(name) = path.file_name() {
@@ -323,11 +851,12 @@ fn refresh_bat_mods() -> Vec<ModViewModel> {
                         mod_map.push(ModViewModel {
                             mod_type: crate::EModType::BatMod,
                             path: path.to_owned(),
-                            name: name.to_str().unwrap().into(),
+                            name: name.to_string_lossy().into(),
                             enabled: false,
                             overlay_enabled: false,
                             gmsts: vec![],
                             txt: None,
+                            diagnostics: vec![],
                         });
                     }
                 }
@@ -336,214 +865,899 @@ fn refresh_bat_mods() -> Vec<ModViewModel> {
     }
 
     // sort by load order
-    if let Some(order) = get_bat_order() {
+    let order = match ini_path {
+        Some(ini_path) => get_start_commands(ini_path)?,
+        None => None,
+    };
+    if let Some(order) = order {
+        // a bat mod listed twice runs twice, the last one decides the load order
+        let mut seen = HashSet::new();
         let mut ordered: Vec<ModViewModel> = vec![];
-        for o in order {
-            if let Some(found) = mod_map.iter_mut().find(|p| p.name == format!("{}.txt", o)) {
+        for o in order.iter().rev() {
+            let found = match o {
+                EStartCommand::Bat(name) => {
+                    let file_name = format!("{}.txt", name);
+                    mod_map
+                        .iter_mut()
+                        .find(|p| p.name.eq_ignore_ascii_case(&file_name))
+                }
+                EStartCommand::Other(_) => None,
+            };
+            let vm = if let Some(found) = found {
                 found.enabled = true;
-                ordered.push(found.clone());
+                found.clone()
+            } else {
+                // keep foreign commands and bat files we don't know
+                ModViewModel::from_command(&o.to_string())
+            };
+            // other commands are kept as typed, duplicates included
+            if vm.mod_type != EModType::BatMod || seen.insert(vm.name.to_lowercase()) {
+                ordered.push(vm);
             }
         }
+        ordered.reverse();
         let mut cnt = 0;
         for m in mod_map {
-            if !ordered.iter().any(|p| p.name == m.name) {
+            if !ordered
+                .iter()
+                .any(|p| p.mod_type == EModType::BatMod && p.name == m.name)
+            {
                 ordered.insert(cnt, m);
                 cnt += 1;
             }
         }
 
-        ordered
+        Ok(ordered)
     } else {
-        mod_map
+        Ok(mod_map)
     }
 }
 
+/// Gets the default StarfieldCustom.ini path in the user's documents
 #[cfg(not(target_arch = "wasm32"))]
-fn get_bat_order() -> Option<Vec<String>> {
+pub fn get_default_ini_path() -> Option<PathBuf> {
+    let user_dirs = UserDirs::new()?;
+    let documents = user_dirs.document_dir()?;
+    Some(
+        PathBuf::from(documents)
+            .join("My Games")
+            .join("Starfield")
+            .join("StarfieldCustom.ini"),
+    )
+}
+
+/// Gets all entries of sStartingConsoleCommand, in load order
+/// Returns None if the ini doesn't exist
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_start_commands(ini_path: &Path) -> Result<Option<Vec<EStartCommand>>> {
     // checks
-    let Some(user_dirs) = UserDirs::new() else {
-        return None;
-    };
-    let Some(documents) = user_dirs.document_dir() else {
-        return None;
-    };
-    let sf_mygames_path = PathBuf::from(documents).join("My Games").join("Starfield");
-    if !sf_mygames_path.exists() {
-        return None;
-    }
-    let ini_path = sf_mygames_path.join("StarfieldCustom.ini");
     if !ini_path.exists() {
-        return None;
-    }
-
-    let mut mods: Vec<String> = vec![];
-    if let Ok(file) = File::open(&ini_path) {
-        for line in io::BufReader::new(file).lines().flatten() {
-            if let Some(args) = line.strip_prefix("sStartingConsoleCommand=") {
-                let args_split: Vec<_> = args.split(';').collect();
-                for arg in args_split {
-                    let trimmed = arg.trim();
-                    if let Some(stripped) = trimmed.strip_prefix("bat ") {
-                        mods.push(stripped.to_owned());
-                    }
-                }
-            }
-        }
+        return Ok(None);
+    }
+
+    let mut entries: Vec<EStartCommand> = vec![];
+    let text = std::fs::read_to_string(ini_path).map_err(|err| Error::io(ini_path, err))?;
+    let ini = Ini::parse(&text);
+    if let Some(value) = ini.get(INI_SECTION, INI_START_COMMAND) {
+        entries = parse_start_command(value);
     }
 
-    Some(mods)
+    Ok(Some(entries))
 }
 
 /// Saves currently edited GMSTs to a file
 #[cfg(not(target_arch = "wasm32"))]
-fn save_to_file(
-    toasts: &mut egui_notify::Toasts,
+pub fn save_to_file(
     gmst_vms: &HashMap<String, EGmstValue>,
-    path: &PathBuf,
+    path: &Path,
     use_ccr: bool,
-) -> bool {
-    // save to file
-    if let Err(err) = std::fs::create_dir_all(path.parent().unwrap()) {
-        toasts.error(format!("Failed to write file: {}", err));
-        return false;
-    }
-
-    if let Ok(mut file) = File::create(path) {
-        // get all edited
-        let mut gmsts = gmst_vms.iter().collect::<Vec<_>>();
-        gmsts.sort_by(|a, b| a.0.cmp(b.0));
-
-        let mut commands: Vec<String> = vec![];
-        for vm in gmsts {
-            // write to file
-            let valuestring = match vm.1 {
-                EGmstValue::Bool(b) => b.to_string(),
-                EGmstValue::Float(f) => f.to_string(),
-                EGmstValue::Int(i) => i.to_string(),
-                EGmstValue::UInt(u) => u.to_string(),
-            };
+    backup: &BackupSettings,
+) -> Result<()> {
+    write_mod_file(path, &[], &get_setgs_commands(gmst_vms), use_ccr, backup)
+}
 
-            let line = match vm.0.contains(':') {
-                true => format!("setgs \"{}\" {}", vm.0, valuestring),
-                false => format!("setgs {} {}", vm.0, valuestring),
-            };
-            commands.push(line);
+/// Gets the GMSTs to save to a mod that is edited: the edited GMSTs and the GMSTs the mod set
+/// when it was loaded, so GMSTs that were reset to their default are written back too
+pub fn get_mod_gmsts(
+    mod_gmsts: &[String],
+    gmst_vms: &[GmstViewModel],
+) -> HashMap<String, EGmstValue> {
+    let mod_gmsts = mod_gmsts
+        .iter()
+        .map(|n| GmstName::parse(n))
+        .collect::<HashSet<_>>();
+    gmst_vms
+        .iter()
+        .filter(|vm| vm.is_edited || mod_gmsts.contains(&vm.name))
+        .map(|vm| (vm.gmst.name.to_owned(), vm.gmst.value.clone()))
+        .collect()
+}
+
+/// Sets GMSTs in a mod file, creating it if needed
+/// Only setgs lines whose value changed are rewritten, new GMSTs are added at the end.
+/// All other lines, comments and the TOML structure of CCR mods are kept
+#[cfg(not(target_arch = "wasm32"))]
+pub fn update_mod_file(
+    gmst_vms: &HashMap<String, EGmstValue>,
+    path: &Path,
+    use_ccr: bool,
+    backup: &BackupSettings,
+) -> Result<()> {
+    if !path.exists() {
+        return save_to_file(gmst_vms, path, use_ccr, backup);
+    }
+
+    let text = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    let mut gmsts = gmst_vms.iter().collect::<Vec<_>>();
+    gmsts.sort_by(|a, b| a.0.cmp(b.0));
+
+    let contents = if use_ccr {
+        update_ccr_text(path, &text, &gmsts)?
+    } else {
+        let mut file = ConsoleFile::parse(&text);
+        for (name, value) in gmsts {
+            let unchanged = file
+                .get_gmst(name)
+                .and_then(|v| parse_gmst(name, v))
+                .as_ref()
+                == Some(value);
+            if !unchanged {
+                file.set_gmst(name, &get_value_string(value));
+            }
         }
+        file.to_string()
+    };
 
-        if use_ccr {
-            let event: CcrEvent = CcrEvent {
-                commands,
-                ..Default::default()
-            };
-            let events: Vec<crate::CcrEvent> = vec![event];
-            let model = CcrModel { event: events };
-            let toml = toml::to_string_pretty(&model).unwrap();
-            if let Err(err) = write!(file, "{}", toml) {
-                toasts.error(format!("Failed to write toml: {}", err));
-                return false;
+    if contents == text {
+        return Ok(());
+    }
+    write_file(path, &contents, backup)
+}
+
+/// Sets GMSTs in the commands of a CCR mod, keeping all other keys, events and formatting
+#[cfg(not(target_arch = "wasm32"))]
+fn update_ccr_text(path: &Path, text: &str, gmsts: &[(&String, &EGmstValue)]) -> Result<String> {
+    // validate against the model first to get a readable error
+    toml::from_str::<CcrModel>(text).map_err(|err| Error::toml(path, text, err))?;
+    let parse_error = |message: String| Error::Parse {
+        path: path.to_owned(),
+        line: None,
+        message,
+    };
+    let mut doc = text
+        .parse::<toml_edit::Document>()
+        .map_err(|err| parse_error(err.to_string()))?;
+
+    // the model requires events, but they may be an inline array
+    let Some(events) = doc
+        .get_mut("event")
+        .and_then(|e| e.as_array_of_tables_mut())
+    else {
+        return Err(parse_error("expected [[event]] tables".to_owned()));
+    };
+
+    // the last command setting each GMST, with its value
+    let mut found: HashMap<GmstName, (usize, usize, Option<EGmstValue>)> = HashMap::default();
+    for (event_idx, event) in events.iter().enumerate() {
+        let Some(commands) = event.get("commands").and_then(|c| c.as_array()) else {
+            continue;
+        };
+        for (cmd_idx, command) in commands.iter().enumerate() {
+            let line = ConsoleLine::parse(command.as_str().unwrap_or_default());
+            if let Some(ECommand::SetGs { name, value }) = line.command.map(|c| c.kind) {
+                let parsed = parse_gmst(&name, &value);
+                found.insert(GmstName::parse(&name), (event_idx, cmd_idx, parsed));
             }
-        } else {
-            for line in commands {
-                if let Err(err) = writeln!(file, "{}", line) {
-                    toasts.error(format!("Failed to write line: {}", err));
-                    return false;
+        }
+    }
+
+    // new GMSTs are added to the first DataLoaded event
+    let target_event = events
+        .iter()
+        .position(|e| e.get("eventType").and_then(|t| t.as_str()) == Some("DataLoaded"));
+    let target_event = match target_event {
+        Some(idx) => idx,
+        None => {
+            let mut table = toml_edit::Table::new();
+            table["eventType"] = toml_edit::value("DataLoaded");
+            table["commands"] = toml_edit::value(toml_edit::Array::new());
+            events.push(table);
+            events.len() - 1
+        }
+    };
+
+    for (name, value) in gmsts {
+        match found.get(&GmstName::parse(name)) {
+            Some((_, _, Some(old))) if old == *value => {}
+            Some((event_idx, cmd_idx, _)) => {
+                let command = events
+                    .get_mut(*event_idx)
+                    .and_then(|e| e.get_mut("commands"))
+                    .and_then(|c| c.as_array_mut())
+                    .and_then(|c| c.get_mut(*cmd_idx));
+                if let Some(command) = command {
+                    let mut line = ConsoleLine::parse(command.as_str().unwrap_or_default());
+                    line.set_arg(1, &get_value_string(value));
+                    let decor = command.decor().clone();
+                    *command = toml_edit::Value::from(line.text);
+                    *command.decor_mut() = decor;
+                }
+            }
+            None => {
+                let Some(event) = events.get_mut(target_event) else {
+                    continue;
+                };
+                let commands = event
+                    .entry("commands")
+                    .or_insert_with(|| toml_edit::value(toml_edit::Array::new()));
+                let Some(commands) = commands.as_array_mut() else {
+                    return Err(parse_error("expected commands to be an array".to_owned()));
+                };
+                // comments after an element are in the prefix of the next one, keep the indent only
+                let indent = commands.iter().last().map(|c| {
+                    let prefix = c
+                        .decor()
+                        .prefix()
+                        .and_then(|p| p.as_str())
+                        .unwrap_or_default();
+                    prefix[prefix.rfind('\n').unwrap_or(0)..].to_owned()
+                });
+                // a comment after the last element stays on its line
+                let trailing = commands.trailing().as_str().unwrap_or_default().to_owned();
+                let prefix = match (indent, trailing.rfind('\n')) {
+                    (Some(indent), Some(newline)) if trailing[..newline].contains('#') => {
+                        commands.set_trailing(&trailing[newline..]);
+                        Some(format!("{}{}", &trailing[..newline], indent))
+                    }
+                    (indent, _) => indent,
+                };
+                commands.push(ConsoleCommand::setgs(name, &get_value_string(value)).to_string());
+                if let (Some(prefix), Some(new)) = (prefix, commands.iter_mut().last()) {
+                    new.decor_mut().set_prefix(prefix);
                 }
             }
         }
-        return true;
     }
-    false
+
+    Ok(doc.to_string())
+}
+
+/// Formats a GMST value for a setgs command
+#[cfg(not(target_arch = "wasm32"))]
+fn get_value_string(value: &EGmstValue) -> String {
+    match value {
+        EGmstValue::Bool(b) => b.to_string(),
+        EGmstValue::Float(f) => f.to_string(),
+        EGmstValue::Int(i) => i.to_string(),
+        EGmstValue::UInt(u) => u.to_string(),
+        EGmstValue::String(s) => s.to_owned(),
+    }
 }
 
-/// Saves all edited gmsts to a text file
-/// and registers that text file in the ini
+/// Gets the setgs commands for a list of GMSTs, sorted by name
 #[cfg(not(target_arch = "wasm32"))]
-fn add_command_to_ini(commands: &[String]) -> io::Result<()> {
-    // checks
-    use std::io::{Error, ErrorKind};
+fn get_setgs_commands(gmst_vms: &HashMap<String, EGmstValue>) -> Vec<String> {
+    let mut gmsts = gmst_vms.iter().collect::<Vec<_>>();
+    gmsts.sort_by(|a, b| a.0.cmp(b.0));
 
-    let user_dirs = UserDirs::new().ok_or(Error::new(ErrorKind::NotFound, "UserDirs not found"))?;
-    let documents = user_dirs
-        .document_dir()
-        .ok_or(Error::new(ErrorKind::NotFound, "document_dir not found"))?;
-    let sf_mygames_path = PathBuf::from(documents).join("My Games").join("Starfield");
-    if !sf_mygames_path.exists() {
-        return Err(Error::new(ErrorKind::NotFound, "My Games not found"));
+    let mut commands: Vec<String> = vec![];
+    for vm in gmsts {
+        let valuestring = get_value_string(vm.1);
+        commands.push(ConsoleCommand::setgs(vm.0, &valuestring).to_string());
     }
-    let ini_path = sf_mygames_path.join("StarfieldCustom.ini");
-    if !ini_path.exists() {
-        return Err(Error::new(
-            ErrorKind::NotFound,
-            "StarfieldCustom.ini not found",
-        ));
+    commands
+}
+
+/// Writes commands to a bat or CCR mod file, with optional header comment lines
+#[cfg(not(target_arch = "wasm32"))]
+fn write_mod_file(
+    path: &Path,
+    header: &[String],
+    commands: &[String],
+    use_ccr: bool,
+    backup: &BackupSettings,
+) -> Result<()> {
+    let mut contents = String::new();
+    if use_ccr {
+        for line in header {
+            contents.push_str(&format!("# {}\n", line));
+        }
+
+        let event: CcrEvent = CcrEvent {
+            commands: commands.to_vec(),
+            ..Default::default()
+        };
+        let events: Vec<crate::CcrEvent> = vec![event];
+        let model = CcrModel { event: events };
+        let toml = toml::to_string_pretty(&model).map_err(|err| {
+            WriteError::new(
+                path,
+                EWriteStage::Serialize,
+                io::Error::new(io::ErrorKind::InvalidData, err),
+            )
+        })?;
+        contents.push_str(&toml);
+    } else {
+        for line in header {
+            contents.push_str(&format!("; {}\n", line));
+        }
+        for line in commands {
+            contents.push_str(&format!("{}\n", line));
+        }
     }
 
-    let mut needs_start_command = true;
-    let mut needs_general_section = true;
-    let mut ini_lines: Vec<String> = vec![];
+    write_file(path, &contents, backup)
+}
 
-    let start_command = get_command_line(commands);
+/// Writes a file atomically, an existing file is backed up first
+#[cfg(not(target_arch = "wasm32"))]
+fn write_file(path: &Path, contents: &str, backup: &BackupSettings) -> Result<()> {
+    backup::backup_file(path, backup)
+        .map_err(|err| WriteError::new(path, EWriteStage::Backup, err))?;
+    write_atomic(path, contents.as_bytes())?;
+    Ok(())
+}
 
-    if let Ok(file) = File::open(&ini_path) {
-        for line in io::BufReader::new(file).lines().flatten() {
-            if line.starts_with("[General]") {
-                // checks
-                needs_general_section = false;
-            }
+/// Sorts mods by the order the game runs them in.
+/// CCR mods run on DataLoaded, before the bat mods of sStartingConsoleCommand,
+/// so bat mods come last in their load order and win
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_load_order<'a>(
+    bat_mods: &'a [ModViewModel],
+    ccr_mods: &'a [ModViewModel],
+) -> Vec<&'a ModViewModel> {
+    ccr_mods
+        .iter()
+        .chain(bat_mods.iter())
+        .filter(|m| m.mod_type != EModType::Command)
+        .collect()
+}
 
-            // modify this line
-            if let Some(_args) = line.strip_prefix("sStartingConsoleCommand=") {
-                needs_start_command = false;
+/// Parses the GMSTs of a mod, skipped lines are logged
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_mod(mod_vm: &ModViewModel) -> Result<HashMap<GmstName, EGmstValue>> {
+    let (map, skipped) = parse_file(&mod_vm.path, mod_vm.mod_type == EModType::CcrMod)?;
+    for diagnostic in skipped {
+        log::warn!("Skipped line in {}: {}", mod_vm.path.display(), diagnostic);
+    }
+    Ok(map)
+}
 
-                // collect current commands
-                ini_lines.push(start_command.to_owned());
-            } else {
-                // everything else gets saved
-                ini_lines.push(line);
+/// Merges the GMSTs of multiple mods in load order, later mods overwrite earlier ones
+/// A GMST written differently keeps the spelling of the first mod
+#[cfg(not(target_arch = "wasm32"))]
+pub fn merge_mods(mods: &[&ModViewModel]) -> Result<HashMap<GmstName, EGmstValue>> {
+    let mut map: HashMap<GmstName, EGmstValue> = HashMap::default();
+    for mod_vm in mods {
+        map.extend(parse_mod(mod_vm)?);
+    }
+    Ok(map)
+}
+
+/// Merges multiple mods in load order and saves them to a single mod file
+/// The file starts with a comment listing all source mods
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_merged_mod(
+    mods: &[&ModViewModel],
+    path: &Path,
+    use_ccr: bool,
+    backup: &BackupSettings,
+) -> Result<()> {
+    let mut header = vec!["Merged from:".to_owned()];
+    for mod_vm in mods {
+        header.push(format!("- {}", mod_vm.name));
+    }
+
+    let map = merge_mods(mods)?
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    write_mod_file(path, &header, &get_setgs_commands(&map), use_ccr, backup)
+}
+
+/// Gets all GMSTs that are set by more than one mod, sorted by name
+/// Only enabled bat mods and all CCR mods can win
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_conflicts(
+    bat_mods: &[ModViewModel],
+    ccr_mods: &[ModViewModel],
+) -> Result<Vec<GmstConflict>> {
+    let mut map: HashMap<GmstName, GmstConflict> = HashMap::default();
+    for mod_vm in get_load_order(bat_mods, ccr_mods) {
+        let is_ccr = mod_vm.mod_type == EModType::CcrMod;
+        let is_active = is_ccr || mod_vm.enabled;
+        for (name, value) in parse_mod(mod_vm)? {
+            // the same GMST may be written differently in each mod
+            let conflict = map.entry(name).or_insert_with_key(|name| GmstConflict {
+                name: name.clone(),
+                values: vec![],
+                winner: None,
+            });
+            conflict.values.push((mod_vm.name.to_owned(), value));
+            if is_active {
+                conflict.winner = Some(mod_vm.name.to_owned());
             }
         }
     }
 
-    if needs_general_section {
-        if needs_start_command {
-            ini_lines.push("".into());
-            ini_lines.push("[General]".into());
-            ini_lines.push(start_command.to_owned());
-        } else {
-            // malformed ini
-            //toasts.error("Ini is malformed");
-        }
+    let mut conflicts = map
+        .into_values()
+        .filter(|c| c.values.len() > 1)
+        .collect::<Vec<_>>();
+    conflicts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(conflicts)
+}
+
+/// Writes the entries of the ini's sStartingConsoleCommand
+/// Only that line is changed, it is added to [General] if missing.
+/// Returns warnings about lines the game ignores
+#[cfg(not(target_arch = "wasm32"))]
+pub fn add_command_to_ini(
+    ini_path: &Path,
+    entries: &[EStartCommand],
+    backup: &BackupSettings,
+) -> Result<Vec<String>> {
+    // checks
+    if !ini_path.exists() {
+        return Err(Error::Ini {
+            path: ini_path.to_owned(),
+            line: None,
+            message: "StarfieldCustom.ini not found".to_owned(),
+        });
     }
 
+    let text = std::fs::read_to_string(ini_path).map_err(|err| Error::io(ini_path, err))?;
+    let mut ini = Ini::parse(&text);
+    let warnings = get_ini_warnings(&ini);
+    ini.set(INI_SECTION, INI_START_COMMAND, &get_start_command(entries));
+
     // write ini
-    match File::create(&ini_path) {
-        Ok(mut file) => {
-            for line in ini_lines {
-                if !needs_general_section && needs_start_command && line == *"[General]" {
-                    writeln!(file, "{}", line)?;
-                    writeln!(file, "{}", start_command)?;
-                    continue;
-                }
+    write_file(ini_path, &ini.to_string(), backup)?;
+    Ok(warnings)
+}
 
-                writeln!(file, "{}", line)?;
+/// Gets warnings about lines in the ini that the game ignores
+#[cfg(not(target_arch = "wasm32"))]
+fn get_ini_warnings(ini: &Ini) -> Vec<String> {
+    let mut warnings = vec![];
+    for (idx, (line, section)) in ini.lines.iter().zip(ini.sections()).enumerate() {
+        match &line.kind {
+            EIniLine::Invalid => warnings.push(format!(
+                "Line {}: invalid ini line '{}'",
+                idx + 1,
+                line.text
+            )),
+            EIniLine::Entry { key, .. }
+                if key.eq_ignore_ascii_case(INI_START_COMMAND)
+                    && !section.is_some_and(|s| s.eq_ignore_ascii_case(INI_SECTION)) =>
+            {
+                warnings.push(format!(
+                    "Line {}: {} is ignored outside of [{}]",
+                    idx + 1,
+                    INI_START_COMMAND,
+                    INI_SECTION
+                ))
             }
-
-            Ok(())
+            _ => {}
         }
-        Err(err) => Err(err),
     }
+    warnings
 }
 
+/// Builds the sStartingConsoleCommand ini line from its entries
 #[cfg(not(target_arch = "wasm32"))]
-fn get_command_line(commands: &[String]) -> String {
-    let mut collected_line = "".to_owned();
-    for c in commands {
-        let mut name = c.to_owned();
-        if c.ends_with(".txt") {
-            name = name[..c.len() - 4].to_owned();
+pub fn get_command_line(entries: &[EStartCommand]) -> String {
+    format!("{}={}", INI_START_COMMAND, get_start_command(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_section_names() {
+        let name = GmstName::parse("\"b3rdPersonAimUseWeaponFOV:ThirdPersonCam\"");
+        assert_eq!(name.name, "b3rdPersonAimUseWeaponFOV");
+        assert_eq!(name.section.as_deref(), Some("ThirdPersonCam"));
+        assert_eq!(name.to_string(), "b3rdPersonAimUseWeaponFOV:ThirdPersonCam");
+
+        assert_eq!(GmstName::parse("fFoo:Bar"), GmstName::parse("\"ffoo:bar\""));
+        assert_ne!(GmstName::parse("fFoo:Bar"), GmstName::parse("fFoo"));
+        let set = HashSet::from([GmstName::parse("fFoo:Bar")]);
+        assert!(set.contains(&GmstName::parse("FFOO:BAR")));
+        assert!(ESectionFilter::None.matches(&GmstName::parse("fFoo")));
+        assert!(ESectionFilter::Section("bar".to_owned()).matches(&GmstName::parse("fFoo:Bar")));
+
+        let names = [
+            "fFoo:Bar".to_owned(),
+            "fJump".to_owned(),
+            "fjump".to_owned(),
+        ];
+        let index = GmstNameIndex::new(&names);
+        assert_eq!(index.find("FJUMP"), Some(&names[1]));
+        assert_eq!(index.find("fFoo"), None);
+        assert_eq!(index.find_base(&GmstName::parse("ffoo")), Some(&names[0]));
+    }
+
+    #[test]
+    fn parses_string_values() {
+        assert_eq!(
+            parse_gmst("sYes", "Sure, why not"),
+            Some(EGmstValue::String("Sure, why not".to_owned()))
+        );
+        assert_eq!(parse_gmst("sYes", "\"Sure\""), None);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn parses_names_ignoring_case() {
+        let dir = test_util::TempDir::new("names");
+        let path = dir.join("my_gmsts.txt");
+        std::fs::write(
+            &path,
+            "\u{feff}setgs iBom 5\nsetgs fJumpHeightMin 1\nsetgs \"fFoo:Bar\" 2\nsetgs fjumpheightmin 3\nsetgs ffoo:bar 4\n",
+        )
+        .unwrap();
+        let (map, skipped) = parse_file(&path, false).unwrap();
+        assert!(skipped.is_empty());
+
+        let map = map
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["fJumpHeightMin"], EGmstValue::Float(3.0));
+        assert_eq!(map["fFoo:Bar"], EGmstValue::Float(4.0));
+        assert_eq!(map["iBom"], EGmstValue::Int(5));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn skips_invalid_setgs_lines() {
+        let dir = test_util::TempDir::new("skipped");
+        let path = dir.join("my_gmsts.txt");
+        std::fs::write(
+            &path,
+            "setgs fA 1\nsetgs bB 1\nsetgs iC 1.5\nsetgs fD\nsetgs bE 0\nsetgs xF 1\n",
+        )
+        .unwrap();
+        let (map, skipped) = parse_file(&path, false).unwrap();
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&GmstName::parse("bB")], EGmstValue::Bool(true));
+        assert_eq!(map[&GmstName::parse("be")], EGmstValue::Bool(false));
+        assert_eq!(
+            skipped.iter().map(|d| d.line).collect::<Vec<_>>(),
+            vec![Some(3), Some(4)]
+        );
+
+        // only unreadable files and broken TOML fail
+        let ccr_path = dir.join("broken.toml");
+        std::fs::write(&ccr_path, "[[event]\n").unwrap();
+        assert!(matches!(
+            parse_file(&ccr_path, true),
+            Err(Error::Toml { line: Some(1), .. })
+        ));
+        assert!(matches!(
+            parse_file(&dir.join("missing.txt"), false),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn appends_to_missing_file() {
+        let dir = test_util::TempDir::new("append");
+        let path = dir.join("my_gmsts.txt");
+        let backup = dir.backup_settings();
+        let gmsts = HashMap::from([("fA".to_owned(), EGmstValue::Float(2.0))]);
+        update_mod_file(&gmsts, &path, false, &backup).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "setgs fA 2\n");
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn updates_ccr_mods_in_place() {
+        let dir = test_util::TempDir::new("update_ccr");
+        let path = dir.join("Ship.toml");
+        let backup = dir.backup_settings();
+        let text = r#"# my ship tweaks
+author = "me"
+
+[[event]]
+eventType = "DataLoaded"
+commands = [
+    "setgs fA 1", # unchanged
+    "setgs fB 1", # changed
+    "tgm", # god mode
+]
+
+[[event]]
+eventType = "DataLoaded"
+delay = 5
+commands = ["setgs fC 1"]
+"#;
+        std::fs::write(&path, text).unwrap();
+        let gmsts = HashMap::from([
+            ("fA".to_owned(), EGmstValue::Float(1.0)),
+            ("fb".to_owned(), EGmstValue::Float(2.0)),
+            ("fD".to_owned(), EGmstValue::Float(3.0)),
+        ]);
+        update_mod_file(&gmsts, &path, true, &backup).unwrap();
+
+        let expected = r#"# my ship tweaks
+author = "me"
+
+[[event]]
+eventType = "DataLoaded"
+commands = [
+    "setgs fA 1", # unchanged
+    "setgs fB 2", # changed
+    "tgm", # god mode
+    "setgs fD 3",
+]
+
+[[event]]
+eventType = "DataLoaded"
+delay = 5
+commands = ["setgs fC 1"]
+"#;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+
+        // inline event arrays are not rewritten
+        std::fs::write(
+            &path,
+            "event = [{ eventType = \"DataLoaded\", commands = [] }]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            update_mod_file(&gmsts, &path, true, &backup),
+            Err(Error::Parse { .. })
+        ));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn updates_bat_mods_in_place() {
+        let dir = test_util::TempDir::new("update_bat");
+        let path = dir.join("my_gmsts.txt");
+        let backup = dir.backup_settings();
+        let text = "; jump\r\nsetgs fA 1\r\ntgm\r\nsetgs fB 1 ; old\r\nsetgs fb 5\r\n";
+        std::fs::write(&path, text).unwrap();
+        let gmsts = HashMap::from([
+            ("fA".to_owned(), EGmstValue::Float(1.0)),
+            ("fB".to_owned(), EGmstValue::Float(2.0)),
+            ("fC".to_owned(), EGmstValue::Float(3.0)),
+        ]);
+        update_mod_file(&gmsts, &path, false, &backup).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "; jump\r\nsetgs fA 1\r\ntgm\r\nsetgs fB 1 ; old\r\nsetgs fb 2\r\nsetgs fC 3\r\n"
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn gets_diagnostics() {
+        let defaults = HashMap::from([
+            ("fA".to_owned(), EGmstValue::Float(1.0)),
+            ("iB".to_owned(), EGmstValue::Int(1)),
+            ("fC:Section".to_owned(), EGmstValue::Float(1.0)),
+        ]);
+        let text = "; comment\nsetgs fA 2\nsetgs fA\nsetgs iB 1.5\nsetgs fUnknown 1\nsetgs fC 1\ntgm\nsetgs sUnknown \"a\"\n";
+        let diagnostics = get_diagnostics(text, false, &defaults);
+
+        let lines = diagnostics
+            .iter()
+            .map(|d| (d.line, d.level))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                (Some(3), EDiagnosticLevel::Warning),
+                (Some(4), EDiagnosticLevel::Warning),
+                (Some(5), EDiagnosticLevel::Warning),
+                (Some(6), EDiagnosticLevel::Warning),
+                (Some(7), EDiagnosticLevel::Info),
+            ]
+        );
+        assert!(diagnostics[1].message.contains("integer"));
+        assert!(diagnostics[2].message.contains("unknown GMST fUnknown"));
+        assert!(diagnostics[3].message.contains("did you mean fC:Section"));
+        assert!(diagnostics[4].message.contains("'tgm'"));
+
+        // a byte order mark is not part of the first command
+        let text = "\u{feff}setgs fA 1\nsetgs iB 2\n";
+        assert!(get_diagnostics(text, false, &defaults).is_empty());
+
+        let text = "[[event]]\neventType = DataLoaded\ncommands = [\"setgs fA 1\"]\n";
+        let diagnostics = get_diagnostics(text, true, &defaults);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(2));
+        assert!(diagnostics[0].message.starts_with("invalid TOML"));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn gets_ccr_command_lines() {
+        let defaults = HashMap::from([("iB".to_owned(), EGmstValue::Int(1))]);
+        let text = r#"[[event]]
+eventType = "DataLoaded"
+commands = [
+    "setgs iB 1.5",
+]
+
+[[event]]
+eventType = "DataLoaded"
+commands = ["setgs iB 1",
+"setgs iB 1.5", "setgs fUnknown \u0031"]
+"#;
+        let lines = get_diagnostics(text, true, &defaults)
+            .iter()
+            .map(|d| d.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![Some(4), Some(10), Some(10)]);
+    }
+
+    #[test]
+    fn parses_start_commands() {
+        let entries = parse_start_command(" BAT My_Mod ; ;bat \"fast ship\";tgm; coc Jemison ;;");
+        assert_eq!(
+            entries,
+            vec![
+                EStartCommand::Bat("My_Mod".to_owned()),
+                EStartCommand::Bat("fast ship".to_owned()),
+                EStartCommand::Other("tgm".to_owned()),
+                EStartCommand::Other("coc Jemison".to_owned()),
+            ]
+        );
+        assert_eq!(
+            get_start_command(&entries),
+            "bat My_Mod;bat \"fast ship\";tgm;coc Jemison;"
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn refreshes_bat_mods_in_load_order() {
+        let dir = test_util::TempDir::new("bat_mods");
+        for name in ["my_mod.txt", "fast ship.txt", "other.txt"] {
+            std::fs::write(dir.join(name), "setgs fA 1\n").unwrap();
+        }
+        let ini_path = dir.join("StarfieldCustom.ini");
+        std::fs::write(
+            &ini_path,
+            "[General]\nsStartingConsoleCommand=bat MY_MOD;tgm;bat \"fast ship\";bat missing;bat my_mod;tgm;\n",
+        )
+        .unwrap();
+        let mods = refresh_bat_mods(dir.path(), Some(&ini_path)).unwrap();
+
+        let rows = mods
+            .iter()
+            .map(|m| (m.name.as_str(), m.enabled))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                ("other.txt", false),
+                ("tgm", true),
+                ("fast ship.txt", true),
+                ("bat missing", true),
+                ("my_mod.txt", true),
+                ("tgm", true),
+            ]
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn merges_mods_in_load_order() {
+        let dir = test_util::TempDir::new("merge");
+        let backup = dir.backup_settings();
+        let get_mod = |name: &str, mod_type: EModType, enabled: bool, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            ModViewModel {
+                mod_type,
+                path,
+                name: name.to_owned(),
+                enabled,
+                overlay_enabled: false,
+                gmsts: vec![],
+                txt: None,
+                diagnostics: vec![],
+            }
+        };
+        let ccr_mods = vec![get_mod(
+            "Ship.toml",
+            EModType::CcrMod,
+            false,
+            "[[event]]\neventType = \"DataLoaded\"\ncommands = [\"setgs fA 1\", \"setgs iB 1\"]\n",
+        )];
+        let bat_mods = vec![
+            get_mod("a.txt", EModType::BatMod, true, "setgs fa 2\nsetgs sC x\n"),
+            get_mod("b.txt", EModType::BatMod, false, "setgs fA 3\nsetgs iB 3\n"),
+            ModViewModel::from_command("tgm"),
+        ];
+        let load_order = get_load_order(&bat_mods, &ccr_mods);
+
+        let merged = merge_mods(&load_order).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[&GmstName::parse("fA")], EGmstValue::Float(3.0));
+        assert_eq!(merged[&GmstName::parse("iB")], EGmstValue::Int(3));
+
+        let path = dir.join("merged.txt");
+        save_merged_mod(&load_order, &path, false, &backup).unwrap();
+        let (saved, skipped) = parse_file(&path, false).unwrap();
+        assert_eq!(saved, merged);
+        assert!(skipped.is_empty());
+
+        // the disabled bat mod can't win
+        let conflicts = get_conflicts(&bat_mods, &ccr_mods).unwrap();
+        let conflicts = conflicts
+            .iter()
+            .map(|c| {
+                let mods = c.values.iter().map(|(m, _)| m.as_str()).collect::<Vec<_>>();
+                (c.name.to_string(), mods, c.winner.as_deref())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conflicts,
+            vec![
+                (
+                    "fA".to_owned(),
+                    vec!["Ship.toml", "a.txt", "b.txt"],
+                    Some("a.txt")
+                ),
+                (
+                    "iB".to_owned(),
+                    vec!["Ship.toml", "b.txt"],
+                    Some("Ship.toml")
+                ),
+            ]
+        );
+        // rows are looked up ignoring the spelling of the first mod
+        let conflicts = get_conflicts(&bat_mods, &ccr_mods).unwrap();
+        assert_eq!(
+            conflicts.binary_search_by(|c| c.name.cmp(&GmstName::parse("FA"))),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn gets_mod_gmsts() {
+        let get_vm = |name: &str, is_edited: bool| GmstViewModel {
+            is_edited,
+            ..test_util::get_vm(name, EGmstValue::Int(1))
+        };
+        let vms = vec![get_vm("iA", true), get_vm("iB", false), get_vm("iC", false)];
+
+        let gmsts = get_mod_gmsts(&["ib".to_owned()], &vms);
+        let mut names = gmsts.keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["iA", "iB"]);
+    }
+
+    #[test]
+    fn validates_mod_names() {
+        let mods = ["my_gmsts.txt", "Ship.toml"];
+        assert_eq!(get_mod_name_error("fast_ship_combat", mods), None);
+        assert!(get_mod_name_error("", mods).is_some());
+        assert!(get_mod_name_error("fast ship", mods).is_some());
+        assert!(get_mod_name_error("fast.txt", mods).is_some());
+        assert!(get_mod_name_error("fast/ship", mods).is_some());
+        assert!(get_mod_name_error("MY_GMSTS", mods).is_some());
+        assert!(get_mod_name_error("ship", mods).is_some());
+        assert!(get_mod_name_error("fast;tgm", mods).is_some());
+        assert!(get_mod_name_error("fast\"ship", mods).is_some());
+        assert!(get_mod_name_error("schiff_für_alle", mods).is_some());
+        assert_eq!(get_mod_name_error("Fast-Ship_2", mods), None);
+        for reserved in ["con", "PRN", "Aux", "nul", "COM1", "lpt9", "com0"] {
+            assert!(get_mod_name_error(reserved, mods).is_some());
         }
-        collected_line += format!("bat {};", name).as_str();
+        assert_eq!(get_mod_name_error("COM10", mods), None);
+        assert_eq!(get_mod_name_error("console", mods), None);
     }
-    let start_command = format!("sStartingConsoleCommand={}", collected_line);
-    start_command
 }
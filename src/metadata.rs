@@ -0,0 +1,269 @@
+//! GMST metadata: descriptions, categories, units and sane value ranges
+//!
+//! The metadata is a TOML file with one `[gmst.<name>]` table per GMST, see `gmst_metadata.toml`.
+//! A user file `gmst_metadata.toml` in the config folder is merged over the embedded one,
+//! so descriptions and ranges can be fixed or added without a new release.
+
+use std::{collections::HashMap, fmt::Display};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::{Error, Result};
+use crate::EGmstValue;
+#[cfg(not(target_arch = "wasm32"))]
+use directories::ProjectDirs;
+
+/// The area of the game a GMST affects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ECategory {
+    Combat,
+    Movement,
+    Ship,
+    Economy,
+    Camera,
+    Character,
+    Ui,
+    /// Any category this version doesn't know
+    #[serde(other)]
+    Other,
+}
+
+impl Display for ECategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ECategory::Combat => "Combat",
+            ECategory::Movement => "Movement",
+            ECategory::Ship => "Ship",
+            ECategory::Economy => "Economy",
+            ECategory::Camera => "Camera",
+            ECategory::Character => "Character",
+            ECategory::Ui => "UI",
+            ECategory::Other => "Other",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GmstMetadata {
+    pub description: Option<String>,
+    pub category: Option<ECategory>,
+    pub unit: Option<String>,
+    /// Lowest sane value
+    pub min: Option<f64>,
+    /// Highest sane value
+    pub max: Option<f64>,
+    /// Community findings
+    pub notes: Option<String>,
+}
+
+impl GmstMetadata {
+    /// Checks if a numeric value is inside the sane range, other values always are
+    pub fn is_in_range(&self, value: &EGmstValue) -> bool {
+        let value = match value {
+            EGmstValue::Float(f) => *f as f64,
+            EGmstValue::Int(i) => *i as f64,
+            EGmstValue::UInt(u) => *u as f64,
+            EGmstValue::Bool(_) | EGmstValue::String(_) => return true,
+        };
+        !matches!(self.min, Some(min) if value < min)
+            && !matches!(self.max, Some(max) if value > max)
+    }
+
+    /// Formats the range, e.g. "0 to 10 m"
+    pub fn get_range_text(&self) -> Option<String> {
+        let range = match (self.min, self.max) {
+            (Some(min), Some(max)) => format!("{} to {}", min, max),
+            (Some(min), None) => format!("at least {}", min),
+            (None, Some(max)) => format!("at most {}", max),
+            (None, None) => return None,
+        };
+        Some(match &self.unit {
+            Some(unit) => format!("{} {}", range, unit),
+            None => range,
+        })
+    }
+
+    /// Builds the tooltip text of the GMST grid
+    pub fn get_tooltip(&self) -> String {
+        let mut lines = vec![];
+        if let Some(description) = &self.description {
+            lines.push(description.to_owned());
+        }
+        if let Some(category) = &self.category {
+            lines.push(format!("Category: {}", category));
+        }
+        match self.get_range_text() {
+            Some(range) => lines.push(format!("Range: {}", range)),
+            None => {
+                if let Some(unit) = &self.unit {
+                    lines.push(format!("Unit: {}", unit));
+                }
+            }
+        }
+        if let Some(notes) = &self.notes {
+            lines.push(format!("Notes: {}", notes));
+        }
+        lines.join("\n")
+    }
+
+    /// Overrides the fields that are set in the other metadata
+    pub fn merge(&mut self, other: GmstMetadata) {
+        let GmstMetadata {
+            description,
+            category,
+            unit,
+            min,
+            max,
+            notes,
+        } = other;
+        self.description = description.or(self.description.take());
+        self.category = category.or(self.category);
+        self.unit = unit.or(self.unit.take());
+        self.min = min.or(self.min);
+        self.max = max.or(self.max);
+        self.notes = notes.or(self.notes.take());
+    }
+}
+
+/// A metadata file
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MetadataDb {
+    pub gmst: HashMap<String, GmstMetadata>,
+}
+
+/// Parses a metadata file
+pub fn parse_metadata_str(text: &str) -> std::result::Result<MetadataDb, toml::de::Error> {
+    toml::from_str(text)
+}
+
+/// Loads the embedded GMST metadata
+pub fn parse_metadata() -> HashMap<String, GmstMetadata> {
+    parse_metadata_str(include_str!("gmst_metadata.toml"))
+        .expect("embedded metadata is valid")
+        .gmst
+}
+
+/// Merges metadata over other metadata, GMST names are matched ignoring case
+pub fn merge_metadata(metadata: &mut HashMap<String, GmstMetadata>, other: MetadataDb) {
+    for (name, meta) in other.gmst {
+        let known = metadata
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(&name))
+            .cloned();
+        match known.and_then(|k| metadata.get_mut(&k)) {
+            Some(existing) => existing.merge(meta),
+            None => {
+                metadata.insert(name, meta);
+            }
+        }
+    }
+}
+
+/// The user metadata file
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_user_metadata_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "sf_gmst_editor").map(|p| p.config_dir().join("gmst_metadata.toml"))
+}
+
+/// Parses a metadata file, a missing file has no metadata
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse_metadata_file(path: &Path) -> Result<MetadataDb> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(MetadataDb::default()),
+        Err(err) => return Err(Error::io(path, err)),
+    };
+    parse_metadata_str(&text).map_err(|err| Error::toml(path, &text, err))
+}
+
+/// Loads the embedded metadata and the user metadata on top of it
+/// A broken user file is skipped and returned as error
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_metadata() -> (HashMap<String, GmstMetadata>, Option<Error>) {
+    let mut metadata = parse_metadata();
+    let Some(path) = get_user_metadata_path() else {
+        return (metadata, None);
+    };
+    match parse_metadata_file(&path) {
+        Ok(user) => {
+            merge_metadata(&mut metadata, user);
+            (metadata, None)
+        }
+        Err(err) => (metadata, Some(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_embedded_metadata() {
+        let metadata = parse_metadata();
+        let defaults = crate::database::merge_gmst_dbs(&crate::database::get_embedded_dbs()).values;
+        for (name, meta) in &metadata {
+            assert!(defaults.contains_key(name), "unknown GMST {}", name);
+            if let Some(value) = defaults.get(name) {
+                assert!(
+                    meta.is_in_range(value),
+                    "default of {} is out of range",
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn checks_ranges() {
+        let db = parse_metadata_str(
+            "[gmst.fA]\ncategory = \"weather\"\nunit = \"m\"\nmin = 0.0\nmax = 10.0\n",
+        )
+        .unwrap();
+        let meta = &db.gmst["fA"];
+
+        assert_eq!(meta.category, Some(ECategory::Other));
+        assert_eq!(meta.get_range_text().as_deref(), Some("0 to 10 m"));
+        assert!(meta.is_in_range(&EGmstValue::Float(10.0)));
+        assert!(!meta.is_in_range(&EGmstValue::Int(-1)));
+        assert!(meta.is_in_range(&EGmstValue::Bool(true)));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn merges_user_metadata() {
+        let dir = crate::test_util::TempDir::new("metadata");
+        let path = dir.join("gmst_metadata.toml");
+        assert_eq!(parse_metadata_file(&path).unwrap(), MetadataDb::default());
+
+        std::fs::write(
+            &path,
+            "[gmst.fjumpheightmin]\nmax = 50.0\n\n[gmst.fNew]\ndescription = \"New\"\n",
+        )
+        .unwrap();
+        let mut metadata = parse_metadata();
+        let embedded = metadata["fJumpHeightMin"].clone();
+        merge_metadata(&mut metadata, parse_metadata_file(&path).unwrap());
+
+        let merged = &metadata["fJumpHeightMin"];
+        assert_eq!(merged.max, Some(50.0));
+        assert_eq!(merged.min, embedded.min);
+        assert_eq!(merged.description, embedded.description);
+        assert_eq!(metadata["fNew"].description.as_deref(), Some("New"));
+        assert!(!metadata.contains_key("fjumpheightmin"));
+
+        std::fs::write(&path, "[gmst.fA]\nmax = \"x\"\n").unwrap();
+        let err = parse_metadata_file(&path).unwrap_err();
+        assert!(matches!(err, Error::Toml { line: Some(2), .. }));
+    }
+}
@@ -0,0 +1,203 @@
+//! Named sets of GMST overrides, e.g. "Hardcore survival" or "Fast ship combat"
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EGmstValue, GmstViewModel};
+
+/// The profile of new users, exported to the file the editor always wrote
+pub const DEFAULT_PROFILE_NAME: &str = "my_gmsts";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct GmstProfile {
+    pub name: String,
+    /// The edited GMSTs and their values
+    pub gmsts: BTreeMap<String, EGmstValue>,
+}
+
+impl GmstProfile {
+    pub fn new(name: &str) -> Self {
+        GmstProfile {
+            name: name.to_owned(),
+            gmsts: BTreeMap::new(),
+        }
+    }
+
+    /// Takes the GMSTs of the grid that differ from their defaults
+    /// GMSTs that are not in the databases are kept, they may be back after a reload.
+    /// GMSTs that show the value of a mod keep the value of the profile
+    pub fn set_gmsts(
+        &mut self,
+        gmst_vms: &[GmstViewModel],
+        default_gmsts: &HashMap<String, EGmstValue>,
+    ) {
+        let is_overlay = |vm: &&GmstViewModel| vm.overlay.as_ref() == Some(&vm.gmst.value);
+        let overlays = gmst_vms
+            .iter()
+            .filter(is_overlay)
+            .map(|vm| vm.gmst.name.as_str())
+            .collect::<HashSet<_>>();
+        self.gmsts.retain(|name, _| {
+            !default_gmsts.contains_key(name) || overlays.contains(name.as_str())
+        });
+        for vm in gmst_vms.iter().filter(|vm| !is_overlay(vm)) {
+            if default_gmsts.get(&vm.gmst.name) != Some(&vm.gmst.value) {
+                self.gmsts
+                    .insert(vm.gmst.name.to_owned(), vm.gmst.value.clone());
+            }
+        }
+    }
+
+    /// Sets the grid to the profile values and all other GMSTs to their defaults
+    /// Returns the GMSTs of the profile that are not in the grid
+    pub fn apply(
+        &self,
+        gmst_vms: &mut [GmstViewModel],
+        default_gmsts: &HashMap<String, EGmstValue>,
+    ) -> Vec<String> {
+        for vm in gmst_vms.iter_mut() {
+            let value = self
+                .gmsts
+                .get(&vm.gmst.name)
+                .or_else(|| default_gmsts.get(&vm.gmst.name));
+            if let Some(value) = value {
+                vm.gmst.value = value.clone();
+                vm.is_edited = default_gmsts.get(&vm.gmst.name) != Some(value);
+            }
+            vm.overlay = None;
+        }
+        self.gmsts
+            .keys()
+            .filter(|name| !default_gmsts.contains_key(*name))
+            .cloned()
+            .collect()
+    }
+
+    /// The file name of the exported mod, without extension
+    /// The console can't run bat files with spaces, so only ascii letters, digits, `-` and `_` are kept
+    pub fn get_file_name(&self) -> String {
+        let name = self
+            .name
+            .trim()
+            .chars()
+            .filter_map(|c| match c {
+                c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => Some(c),
+                c if c.is_whitespace() => Some('_'),
+                _ => None,
+            })
+            .collect::<String>();
+        match name.is_empty() {
+            true => DEFAULT_PROFILE_NAME.to_owned(),
+            false => name,
+        }
+    }
+}
+
+/// Checks if a profile name is taken, ignoring case
+pub fn is_profile_name_taken(profiles: &[GmstProfile], name: &str) -> bool {
+    profiles
+        .iter()
+        .any(|p| p.name.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+/// Appends a number to a name until no profile has it, e.g. "Fast ship combat (2)"
+pub fn get_unique_profile_name(profiles: &[GmstProfile], name: &str) -> String {
+    let mut unique = name.to_owned();
+    let mut i = 2;
+    while is_profile_name_taken(profiles, &unique) {
+        unique = format!("{} ({})", name, i);
+        i += 1;
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::get_vm;
+
+    #[test]
+    fn applies_profiles() {
+        let defaults = HashMap::from([
+            ("fA".to_owned(), EGmstValue::Float(1.0)),
+            ("iB".to_owned(), EGmstValue::Int(2)),
+        ]);
+        let mut vms = vec![
+            get_vm("fA", EGmstValue::Float(5.0)),
+            get_vm("iB", EGmstValue::Int(2)),
+        ];
+
+        let mut profile = GmstProfile::new("Hardcore survival");
+        profile
+            .gmsts
+            .insert("iMissing".to_owned(), EGmstValue::Int(3));
+        profile.set_gmsts(&vms, &defaults);
+        assert_eq!(
+            profile.gmsts,
+            BTreeMap::from([
+                ("fA".to_owned(), EGmstValue::Float(5.0)),
+                ("iMissing".to_owned(), EGmstValue::Int(3))
+            ])
+        );
+
+        let mut other = GmstProfile::new("Fast ship combat");
+        other.gmsts.insert("iB".to_owned(), EGmstValue::Int(3));
+        other
+            .gmsts
+            .insert("iMissing".to_owned(), EGmstValue::Int(3));
+        assert_eq!(other.apply(&mut vms, &defaults), vec!["iMissing"]);
+        assert_eq!(vms[0].gmst.value, EGmstValue::Float(1.0));
+        assert!(!vms[0].is_edited);
+        assert_eq!(vms[1].gmst.value, EGmstValue::Int(3));
+        assert!(vms[1].is_edited);
+    }
+
+    #[test]
+    fn ignores_mod_values() {
+        let defaults = HashMap::from([
+            ("fA".to_owned(), EGmstValue::Float(1.0)),
+            ("iB".to_owned(), EGmstValue::Int(2)),
+            ("iC".to_owned(), EGmstValue::Int(3)),
+        ]);
+        let mut vms = vec![
+            get_vm("fA", EGmstValue::Float(5.0)),
+            get_vm("iB", EGmstValue::Int(4)),
+            get_vm("iC", EGmstValue::Int(6)),
+        ];
+        // a mod shows iB and iC, iC was edited since
+        vms[1].overlay = Some(EGmstValue::Int(4));
+        vms[2].overlay = Some(EGmstValue::Int(5));
+
+        let mut profile = GmstProfile::new("Hardcore survival");
+        profile.gmsts.insert("iB".to_owned(), EGmstValue::Int(7));
+        profile.set_gmsts(&vms, &defaults);
+        assert_eq!(
+            profile.gmsts,
+            BTreeMap::from([
+                ("fA".to_owned(), EGmstValue::Float(5.0)),
+                ("iB".to_owned(), EGmstValue::Int(7)),
+                ("iC".to_owned(), EGmstValue::Int(6)),
+            ])
+        );
+
+        profile.apply(&mut vms, &defaults);
+        assert_eq!(vms[1].gmst.value, EGmstValue::Int(7));
+        assert!(vms.iter().all(|vm| vm.overlay.is_none()));
+    }
+
+    #[test]
+    fn names_profiles() {
+        let profiles = vec![GmstProfile::new("Hardcore survival")];
+        assert_eq!(profiles[0].get_file_name(), "Hardcore_survival");
+        assert_eq!(GmstProfile::new("Ship: 2x!").get_file_name(), "Ship_2x");
+        assert_eq!(GmstProfile::new("?!").get_file_name(), DEFAULT_PROFILE_NAME);
+
+        assert_eq!(
+            get_unique_profile_name(&profiles, "hardcore Survival"),
+            "hardcore Survival (2)"
+        );
+        assert_eq!(get_unique_profile_name(&profiles, "Easy"), "Easy");
+    }
+}
@@ -0,0 +1,71 @@
+//! Helpers shared by the unit tests
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::backup::BackupSettings;
+use crate::{EGmstValue, Gmst, GmstName, GmstViewModel};
+
+static TEMP_DIR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory that is unique per test and deleted on drop, also when an assert fails
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "sf_gmst_{}_{}_{}",
+            name,
+            std::process::id(),
+            TEMP_DIR_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        // left over from a killed test run
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("failed to create the temp dir");
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.path.join(path)
+    }
+
+    /// Backup settings in this directory that keep no backups
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn backup_settings(&self) -> BackupSettings {
+        BackupSettings {
+            dir: self.join("backups"),
+            retention: 0,
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A GMST row of the database view that is not edited
+pub fn get_vm(name: &str, value: EGmstValue) -> GmstViewModel {
+    GmstViewModel {
+        gmst: Gmst {
+            name: name.to_owned(),
+            value,
+        },
+        name: GmstName::parse(name),
+        is_edited: false,
+        overlay: None,
+        metadata: None,
+        sources: vec![],
+    }
+}